@@ -23,17 +23,29 @@ pub struct LWRGL {
     last_mouse_x: Option<f64>,
     last_mouse_y: Option<f64>,
     keys_pressed: HashSet<Key>,
-    mouse_button_events: VecDeque<(i32, bool)>,
-    cur_button_event: Option<(i32, bool)>,
+    mouse_events: VecDeque<(i32, bool, i32)>, // button, state, wheel delta
+    cur_mouse_event: Option<(i32, bool, i32)>,
+    key_events: VecDeque<(Key, bool)>,
+    cur_key_event: Option<(Key, bool)>,
 }
 
 impl LWRGL {
     pub fn new(width: u32, height: u32) -> LWRGL {
+        LWRGL::create(width, height, true)
+    }
+
+    /// Creates a context with an invisible window, for rendering without showing anything.
+    pub fn new_hidden(width: u32, height: u32) -> LWRGL {
+        LWRGL::create(width, height, false)
+    }
+
+    fn create(width: u32, height: u32, visible: bool) -> LWRGL {
         let mut glfw = glfw::init(glfw::FAIL_ON_ERRORS).unwrap();
         glfw.window_hint(glfw::WindowHint::ContextVersion(1, 1));
         glfw.window_hint(glfw::WindowHint::OpenGlProfile(
             glfw::OpenGlProfileHint::Any,
         ));
+        glfw.window_hint(glfw::WindowHint::Visible(visible));
 
         let (mut window, events) = glfw
             .create_window(width, height, "Game", glfw::WindowMode::Windowed)
@@ -43,6 +55,7 @@ impl LWRGL {
         window.set_key_polling(true);
         window.set_framebuffer_size_polling(true);
         window.set_mouse_button_polling(true);
+        window.set_scroll_polling(true);
         glfw.set_swap_interval(glfw::SwapInterval::None);
 
         LWRGL {
@@ -53,8 +66,10 @@ impl LWRGL {
             last_mouse_x: None,
             last_mouse_y: None,
             keys_pressed: HashSet::new(),
-            mouse_button_events: VecDeque::new(),
-            cur_button_event: None,
+            mouse_events: VecDeque::new(),
+            cur_mouse_event: None,
+            key_events: VecDeque::new(),
+            cur_key_event: None,
         }
     }
 
@@ -80,9 +95,11 @@ impl LWRGL {
                 },
                 glfw::WindowEvent::Key(key, _, Action::Press, _) => {
                     self.keys_pressed.insert(key);
+                    self.key_events.push_back((key, true));
                 }
                 glfw::WindowEvent::Key(key, _, Action::Release, _) => {
                     self.keys_pressed.remove(&key);
+                    self.key_events.push_back((key, false));
                 }
                 glfw::WindowEvent::MouseButton(button, Action::Press, _) => {
                     let button = match button {
@@ -95,7 +112,7 @@ impl LWRGL {
                         MouseButton::Button7 => 6,
                         MouseButton::Button8 => 7,
                     };
-                    self.mouse_button_events.push_back((button, true));
+                    self.mouse_events.push_back((button, true, 0));
                 }
                 glfw::WindowEvent::MouseButton(button, Action::Release, _) => {
                     let button = match button {
@@ -108,7 +125,10 @@ impl LWRGL {
                         MouseButton::Button7 => 6,
                         MouseButton::Button8 => 7,
                     };
-                    self.mouse_button_events.push_back((button, false));
+                    self.mouse_events.push_back((button, false, 0));
+                }
+                glfw::WindowEvent::Scroll(_, y) if y != 0.0 => {
+                    self.mouse_events.push_back((-1, false, y.signum() as i32));
                 }
                 _ => {}
             }
@@ -151,17 +171,17 @@ impl LWRGL {
     }
 
     pub fn mouse_next(&mut self) -> bool {
-        if self.mouse_button_events.is_empty() {
+        if self.mouse_events.is_empty() {
             return false;
         }
 
-        self.cur_button_event = self.mouse_button_events.pop_front();
+        self.cur_mouse_event = self.mouse_events.pop_front();
 
         true
     }
 
     pub fn mouse_event_button(&self) -> i32 {
-        if let Some(event) = self.cur_button_event {
+        if let Some(event) = self.cur_mouse_event {
             event.0
         } else {
             -1
@@ -169,7 +189,37 @@ impl LWRGL {
     }
 
     pub fn mouse_event_button_state(&self) -> bool {
-        if let Some(event) = self.cur_button_event {
+        if let Some(event) = self.cur_mouse_event {
+            event.1
+        } else {
+            false
+        }
+    }
+
+    pub fn mouse_event_dwheel(&self) -> i32 {
+        if let Some(event) = self.cur_mouse_event {
+            event.2
+        } else {
+            0
+        }
+    }
+
+    pub fn keyboard_next(&mut self) -> bool {
+        if self.key_events.is_empty() {
+            return false;
+        }
+
+        self.cur_key_event = self.key_events.pop_front();
+
+        true
+    }
+
+    pub fn keyboard_event_key(&self) -> Option<Key> {
+        self.cur_key_event.map(|event| event.0)
+    }
+
+    pub fn keyboard_event_key_state(&self) -> bool {
+        if let Some(event) = self.cur_key_event {
             event.1
         } else {
             false
@@ -0,0 +1,146 @@
+use std::any::Any;
+
+use crate::entity::Entity;
+
+/// Goals say which parts of the mob they take over, those sharing a part do not run together.
+pub const MOVE: u8 = 1;
+pub const LOOK: u8 = 2;
+
+/// What the running goals want the mob to do this tick.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Intent {
+    pub walk: Option<f32>, // direction to walk in as rotation around y, 0 is towards +z
+    pub jump: bool,
+    pub look_at: Option<[f32; 3]>,
+}
+
+pub trait Goal: Any {
+    /// Which of `MOVE` and `LOOK` the goal takes over.
+    fn controls(&self) -> u8;
+    /// Whether the goal wants to start running.
+    fn can_start(&mut self, mob: &Entity) -> bool;
+    /// Whether the running goal wants to keep going.
+    fn can_continue(&mut self, mob: &Entity) -> bool {
+        self.can_start(mob)
+    }
+    fn start(&mut self, _mob: &Entity) {}
+    fn stop(&mut self) {}
+    fn tick(&mut self, mob: &Entity, intent: &mut Intent);
+}
+
+/// A mob's goals by priority. Goals win over the ones added after them that need the same parts
+/// of the mob, stopping them if they are running.
+pub struct Goals {
+    goals: Vec<(Box<dyn Goal>, bool)>, // and whether they are running
+}
+
+impl Goals {
+    pub fn new() -> Goals {
+        Goals { goals: vec![] }
+    }
+
+    pub fn add(mut self, goal: impl Goal + 'static) -> Goals {
+        self.goals.push((Box::new(goal), false));
+        self
+    }
+
+    /// The first goal of type `T`, for getting at its state.
+    pub fn get<T: Goal>(&self) -> Option<&T> {
+        self.goals
+            .iter()
+            .find_map(|(goal, _)| (goal.as_ref() as &dyn Any).downcast_ref())
+    }
+
+    pub fn get_mut<T: Goal>(&mut self) -> Option<&mut T> {
+        self.goals
+            .iter_mut()
+            .find_map(|(goal, _)| (goal.as_mut() as &mut dyn Any).downcast_mut())
+    }
+
+    /// Picks the goals to run and lets them decide what `mob` does.
+    pub fn tick(&mut self, mob: &Entity) -> Intent {
+        let mut taken = 0;
+        for (goal, running) in &mut self.goals {
+            let free = goal.controls() & taken == 0;
+            if *running && !(free && goal.can_continue(mob)) {
+                goal.stop();
+                *running = false;
+            }
+            if !*running && free && goal.can_start(mob) {
+                goal.start(mob);
+                *running = true;
+            }
+            if *running {
+                taken |= goal.controls();
+            }
+        }
+        let mut intent = Intent::default();
+        for (goal, running) in &mut self.goals {
+            if *running {
+                goal.tick(mob, &mut intent);
+            }
+        }
+        intent
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::Cell, cell::RefCell, rc::Rc};
+
+    use super::*;
+    use crate::level::level::Level;
+
+    struct Fixed {
+        controls: u8,
+        wants: Rc<Cell<bool>>,
+        walk: f32,
+    }
+
+    impl Goal for Fixed {
+        fn controls(&self) -> u8 {
+            self.controls
+        }
+
+        fn can_start(&mut self, _mob: &Entity) -> bool {
+            self.wants.get()
+        }
+
+        fn tick(&mut self, _mob: &Entity, intent: &mut Intent) {
+            if self.controls & MOVE != 0 {
+                intent.walk = Some(self.walk);
+            }
+            if self.controls & LOOK != 0 {
+                intent.look_at = Some([self.walk; 3]);
+            }
+        }
+    }
+
+    fn goal(controls: u8, walk: f32) -> (Fixed, Rc<Cell<bool>>) {
+        let wants = Rc::new(Cell::new(true));
+        let goal = Fixed {
+            controls,
+            wants: Rc::clone(&wants),
+            walk,
+        };
+        (goal, wants)
+    }
+
+    #[test]
+    fn runs_the_first_goal_for_each_control() {
+        let mob = Entity::new(Rc::new(RefCell::new(Level::with_dir(64, None))));
+        let (chase, chasing) = goal(MOVE, 1.0);
+        let (wander, _) = goal(MOVE, 2.0);
+        let (look, _) = goal(LOOK, 3.0);
+        let mut goals = Goals::new().add(chase).add(wander).add(look);
+
+        let intent = goals.tick(&mob);
+        assert_eq!(intent.walk, Some(1.0));
+        assert_eq!(intent.look_at, Some([3.0; 3]));
+
+        chasing.set(false);
+        assert_eq!(goals.tick(&mob).walk, Some(2.0));
+        chasing.set(true);
+        assert_eq!(goals.tick(&mob).walk, Some(1.0));
+    }
+}
@@ -0,0 +1,252 @@
+use crate::entity::Entity;
+use crate::level::fluid::Fluid;
+use crate::phys::aabb::AABB;
+
+use super::goal::{Goal, Intent, LOOK, MOVE};
+use super::path::{self, Node, Walker};
+
+/// Ticks between looking for a new way to the target.
+const REPATH_TICKS: i32 = 20;
+/// Tiles looked at when finding a way, enough for a few turns within a chase radius.
+const MAX_PATH_NODES: usize = 600;
+
+/// The player, if they are within `radius` blocks of `mob`.
+fn player_near(mob: &Entity, radius: f32) -> Option<AABB> {
+    let player = mob.level.borrow().player_bb?;
+    let xd = (player.x0 + player.x1) / 2.0 - mob.x;
+    let yd = player.y0 - mob.bb.y0;
+    let zd = (player.z0 + player.z1) / 2.0 - mob.z;
+    if xd * xd + yd * yd + zd * zd < radius * radius {
+        Some(player)
+    } else {
+        None
+    }
+}
+
+/// Whether `mob` walked into a wall during its last move.
+fn blocked(mob: &Entity) -> bool {
+    mob.collision_normal[0] != 0.0 || mob.collision_normal[2] != 0.0
+}
+
+/// Walks around aimlessly, drifting from one direction into another and jumping now and then.
+pub struct WanderGoal {
+    pub rot: f32,   // direction it walks in, see `Intent::walk`
+    pub rot_a: f32, // how fast it drifts off that direction
+}
+
+impl WanderGoal {
+    pub fn new() -> WanderGoal {
+        WanderGoal {
+            rot: rand::random::<f32>() * std::f32::consts::PI * 2.0,
+            rot_a: (rand::random::<f32>() + 1.0) * 0.01,
+        }
+    }
+}
+
+impl Goal for WanderGoal {
+    fn controls(&self) -> u8 {
+        MOVE
+    }
+
+    fn can_start(&mut self, _mob: &Entity) -> bool {
+        true
+    }
+
+    fn tick(&mut self, _mob: &Entity, intent: &mut Intent) {
+        self.rot += self.rot_a;
+        self.rot_a = (self.rot_a as f64 * 0.99) as f32;
+        self.rot_a = (self.rot_a as f64
+            + (rand::random::<f64>() - rand::random::<f64>())
+                * rand::random::<f64>()
+                * rand::random::<f64>()
+                * 0.01) as f32;
+        intent.walk = Some(self.rot);
+        intent.jump = rand::random::<f64>() < 0.01;
+    }
+}
+
+/// Stands still for a moment every now and then.
+pub struct IdleGoal {
+    ticks: i32, // left to stand still
+}
+
+impl IdleGoal {
+    pub fn new() -> IdleGoal {
+        IdleGoal { ticks: 0 }
+    }
+}
+
+impl Goal for IdleGoal {
+    fn controls(&self) -> u8 {
+        MOVE
+    }
+
+    fn can_start(&mut self, mob: &Entity) -> bool {
+        mob.on_ground && rand::random::<f32>() < 0.002
+    }
+
+    fn can_continue(&mut self, _mob: &Entity) -> bool {
+        self.ticks > 0
+    }
+
+    fn start(&mut self, _mob: &Entity) {
+        self.ticks = 60 + (rand::random::<f32>() * 120.0) as i32;
+    }
+
+    fn tick(&mut self, _mob: &Entity, _intent: &mut Intent) {
+        self.ticks -= 1;
+    }
+}
+
+/// Goes after the player within `radius` along the way the pathfinder finds.
+pub struct ChaseGoal {
+    walker: Walker,
+    radius: f32,
+    path: Vec<Node>,   // tiles on the way to the player, next first
+    repath_ticks: i32, // until the path is found again
+}
+
+impl ChaseGoal {
+    pub fn new(walker: Walker, radius: f32) -> ChaseGoal {
+        ChaseGoal {
+            walker,
+            radius,
+            path: vec![],
+            repath_ticks: 0,
+        }
+    }
+}
+
+impl Goal for ChaseGoal {
+    fn controls(&self) -> u8 {
+        MOVE
+    }
+
+    fn can_start(&mut self, mob: &Entity) -> bool {
+        player_near(mob, self.radius).is_some()
+    }
+
+    fn stop(&mut self) {
+        self.path.clear();
+        self.repath_ticks = 0;
+    }
+
+    fn tick(&mut self, mob: &Entity, intent: &mut Intent) {
+        let target = match player_near(mob, self.radius) {
+            Some(target) => target,
+            None => return,
+        };
+        let from = path::node_at(&mob.bb);
+        self.repath_ticks -= 1;
+        if self.repath_ticks <= 0 {
+            let level = mob.level.borrow();
+            self.path = path::find_path(
+                &level,
+                &self.walker,
+                from,
+                path::node_at(&target),
+                MAX_PATH_NODES,
+            );
+            self.repath_ticks = REPATH_TICKS;
+        }
+        while let Some(&(x, y, z)) = self.path.first() {
+            let xd = x as f32 + 0.5 - mob.x;
+            let zd = z as f32 + 0.5 - mob.z;
+            if xd * xd + zd * zd > 0.3 * 0.3 || y != from.1 {
+                break;
+            }
+            self.path.remove(0);
+        }
+        let (xd, zd, up) = match self.path.first() {
+            Some(&(x, y, z)) => (x as f32 + 0.5 - mob.x, z as f32 + 0.5 - mob.z, y > from.1),
+            // as close as it gets, walk straight at it
+            None => (
+                (target.x0 + target.x1) / 2.0 - mob.x,
+                (target.z0 + target.z1) / 2.0 - mob.z,
+                false,
+            ),
+        };
+        intent.walk = Some(xd.atan2(zd));
+        intent.jump = up || blocked(mob);
+    }
+}
+
+/// Runs away from whatever `threat` finds near the mob, by its x and z.
+pub struct FleeGoal {
+    threat: fn(&Entity) -> Option<(f32, f32)>,
+}
+
+impl FleeGoal {
+    pub fn new(threat: fn(&Entity) -> Option<(f32, f32)>) -> FleeGoal {
+        FleeGoal { threat }
+    }
+}
+
+impl Goal for FleeGoal {
+    fn controls(&self) -> u8 {
+        MOVE
+    }
+
+    fn can_start(&mut self, mob: &Entity) -> bool {
+        (self.threat)(mob).is_some()
+    }
+
+    fn tick(&mut self, mob: &Entity, intent: &mut Intent) {
+        if let Some((x, z)) = (self.threat)(mob) {
+            intent.walk = Some((mob.x - x).atan2(mob.z - z));
+            intent.jump = blocked(mob);
+        }
+    }
+}
+
+/// The middle of the closest lava tile within two blocks of `mob`'s feet, a threat to flee.
+pub fn nearby_lava(mob: &Entity) -> Option<(f32, f32)> {
+    let level = mob.level.borrow();
+    let (x, y, z) = path::node_at(&mob.bb);
+    let mut closest: Option<(i32, (f32, f32))> = None;
+    for xt in x - 2..=x + 2 {
+        for zt in z - 2..=z + 2 {
+            for yt in y - 1..=y {
+                if Fluid::of(level.get_tile(xt, yt, zt)) != Some(Fluid::Lava) {
+                    continue;
+                }
+                let dist = (xt - x).pow(2) + (zt - z).pow(2);
+                if closest.is_none_or(|(d, _)| dist < d) {
+                    closest = Some((dist, (xt as f32 + 0.5, zt as f32 + 0.5)));
+                }
+            }
+        }
+    }
+    closest.map(|(_, pos)| pos)
+}
+
+/// Keeps looking at the player's eyes while they are within `radius`.
+pub struct LookAtPlayerGoal {
+    radius: f32,
+}
+
+impl LookAtPlayerGoal {
+    pub fn new(radius: f32) -> LookAtPlayerGoal {
+        LookAtPlayerGoal { radius }
+    }
+}
+
+impl Goal for LookAtPlayerGoal {
+    fn controls(&self) -> u8 {
+        LOOK
+    }
+
+    fn can_start(&mut self, mob: &Entity) -> bool {
+        player_near(mob, self.radius).is_some()
+    }
+
+    fn tick(&mut self, mob: &Entity, intent: &mut Intent) {
+        if let Some(player) = player_near(mob, self.radius) {
+            intent.look_at = Some([
+                (player.x0 + player.x1) / 2.0,
+                player.y0 + 1.62,
+                (player.z0 + player.z1) / 2.0,
+            ]);
+        }
+    }
+}
@@ -0,0 +1,3 @@
+pub mod goal;
+pub mod goals;
+pub mod path;
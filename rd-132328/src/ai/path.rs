@@ -0,0 +1,201 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+
+use crate::entity::{AIR_DRAG, GRAVITY};
+use crate::level::{fluid::Fluid, level::Level};
+use crate::phys::aabb::AABB;
+
+/// A tile an entity can stand in, the one its feet are in.
+pub type Node = (i32, i32, i32);
+
+/// The tile the feet of an entity with bounding box `bb` are in.
+pub fn node_at(bb: &AABB) -> Node {
+    (
+        ((bb.x0 + bb.x1) / 2.0).floor() as i32,
+        (bb.y0 + 0.01).floor() as i32,
+        ((bb.z0 + bb.z1) / 2.0).floor() as i32,
+    )
+}
+
+const NEIGHBORS: [(i32, i32); 4] = [(-1, 0), (1, 0), (0, -1), (0, 1)];
+
+/// What an entity can get over when walking, worked out from its size and how it moves.
+#[derive(Debug, Clone, Copy)]
+pub struct Walker {
+    pub height: i32,      // tiles of room it needs
+    pub jump_height: i32, // tiles it can jump or step up
+    pub max_drop: i32,    // tiles it is willing to drop down
+}
+
+impl Walker {
+    /// A walker `height` tall that jumps with `jump_speed` against gravity and air drag, or steps
+    /// up ledges of `step_height` when that is higher.
+    pub fn new(height: f32, jump_speed: f32, step_height: f32, max_drop: i32) -> Walker {
+        let mut y = 0.0;
+        let mut yd = jump_speed;
+        while yd > 0.0 {
+            yd -= GRAVITY;
+            y += yd;
+            yd *= AIR_DRAG;
+        }
+        Walker {
+            height: height.ceil() as i32,
+            jump_height: y.max(step_height).floor() as i32,
+            max_drop,
+        }
+    }
+
+    fn fits(&self, level: &Level, (x, y, z): Node) -> bool {
+        (y..y + self.height).all(|y| {
+            !level.is_solid_tile(x, y, z) && Fluid::of(level.get_tile(x, y, z)) != Some(Fluid::Lava)
+        })
+    }
+
+    fn can_stand(&self, level: &Level, (x, y, z): Node) -> bool {
+        self.fits(level, (x, y, z)) && level.is_solid_tile(x, y - 1, z)
+    }
+
+    /// Where it can get to from `node` in one move: a step sideways onto the same level, up a
+    /// ledge it can jump or down a drop it is willing to take.
+    fn moves(&self, level: &Level, (x, y, z): Node) -> Vec<(Node, i32)> {
+        let mut moves = vec![];
+        for (xd, zd) in NEIGHBORS {
+            let (x, z) = (x + xd, z + zd);
+            if let Some(up) = (1..=self.jump_height)
+                .find(|up| self.can_stand(level, (x, y + up, z)))
+                .filter(|up| self.fits(level, (x - xd, y + up, z - zd)))
+            {
+                moves.push(((x, y + up, z), 1 + up));
+            }
+            if !self.fits(level, (x, y, z)) {
+                continue;
+            }
+            if let Some(down) =
+                (0..=self.max_drop).find(|down| level.is_solid_tile(x, y - down - 1, z))
+            {
+                if self.fits(level, (x, y - down, z)) {
+                    moves.push(((x, y - down, z), 1 + down));
+                }
+            }
+        }
+        moves
+    }
+}
+
+/// Finds a way from `from` to `to` with A*, looking at no more than `max_nodes` tiles. When `to`
+/// cannot be reached in time, the way leads to the closest tile found instead. The path leaves
+/// out `from` and is empty when there is nowhere better to go.
+pub fn find_path(
+    level: &Level,
+    walker: &Walker,
+    from: Node,
+    to: Node,
+    max_nodes: usize,
+) -> Vec<Node> {
+    let estimate = |(x, y, z): Node| (x - to.0).abs() + (y - to.1).abs() + (z - to.2).abs();
+    let mut open = BinaryHeap::new();
+    let mut came_from: HashMap<Node, Node> = HashMap::new();
+    let mut cost: HashMap<Node, i32> = HashMap::new();
+    let mut closest = (estimate(from), from);
+    open.push(Reverse((estimate(from), from)));
+    cost.insert(from, 0);
+
+    let mut visited = 0;
+    while let Some(Reverse((_, node))) = open.pop() {
+        if node == to {
+            closest = (0, node);
+            break;
+        }
+        visited += 1;
+        if visited > max_nodes {
+            break;
+        }
+        closest = closest.min((estimate(node), node));
+        for (next, step) in walker.moves(level, node) {
+            let c = cost[&node] + step;
+            if cost.get(&next).is_some_and(|old| *old <= c) {
+                continue;
+            }
+            cost.insert(next, c);
+            came_from.insert(next, node);
+            open.push(Reverse((c + estimate(next), next)));
+        }
+    }
+
+    let mut path = vec![];
+    let mut node = closest.1;
+    while node != from {
+        path.push(node);
+        node = came_from[&node];
+    }
+    path.reverse();
+    path
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::level::tile;
+
+    fn flat_level() -> (Level, i32) {
+        let mut level = Level::with_dir(64, None);
+        level.update_columns(0.0, 0.0);
+        let ground = level.depth * 2 / 3 + 1;
+        (level, ground)
+    }
+
+    fn zombie() -> Walker {
+        Walker::new(1.8, 0.12, 0.5, 3)
+    }
+
+    #[test]
+    fn jumps_one_tile_but_not_two() {
+        assert_eq!(zombie().jump_height, 1);
+        assert_eq!(zombie().height, 2);
+    }
+
+    #[test]
+    fn walks_around_a_wall() {
+        let (mut level, y) = flat_level();
+        for z in -3..=3 {
+            for yy in y..y + 3 {
+                level.set_tile(2, yy, z, tile::ROCK.id);
+            }
+        }
+        let path = find_path(&level, &zombie(), (0, y, 0), (4, y, 0), 1000);
+        assert_eq!(path.last(), Some(&(4, y, 0)));
+        assert!(path.iter().all(|(x, _, z)| *x != 2 || z.abs() > 3));
+        for pair in path.windows(2) {
+            let (a, b) = (pair[0], pair[1]);
+            assert_eq!((a.0 - b.0).abs() + (a.2 - b.2).abs(), 1);
+        }
+    }
+
+    #[test]
+    fn climbs_steps_and_drops_down() {
+        let (mut level, y) = flat_level();
+        for z in -3..=3 {
+            level.set_tile(1, y, z, tile::ROCK.id);
+            level.set_tile(2, y, z, tile::ROCK.id);
+            level.set_tile(2, y + 1, z, tile::ROCK.id);
+        }
+        let path = find_path(&level, &zombie(), (0, y, 0), (4, y, 0), 1000);
+        assert_eq!(&path[..2], [(1, y + 1, 0), (2, y + 2, 0)]);
+        assert_eq!(path.last(), Some(&(4, y, 0)));
+    }
+
+    #[test]
+    fn gets_as_close_as_it_can_over_walls_too_high() {
+        let (mut level, y) = flat_level();
+        for x in -2..=2i32 {
+            for z in -2..=2i32 {
+                if x.abs() == 2 || z.abs() == 2 {
+                    level.set_tile(x, y, z, tile::ROCK.id);
+                    level.set_tile(x, y + 1, z, tile::ROCK.id);
+                }
+            }
+        }
+        let path = find_path(&level, &zombie(), (0, y, 0), (4, y, 0), 1000);
+        assert_eq!(path.last(), Some(&(1, y, 0)));
+    }
+}
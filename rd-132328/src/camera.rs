@@ -0,0 +1,375 @@
+use std::f32::consts::PI;
+
+use lwrgl::glfw::Key;
+use lwrgl::LWRGL;
+
+use crate::{
+    entity::Entity,
+    level::{frustrum::Frustum, level::Level},
+};
+
+/// A 4x4 matrix in OpenGL's column-major order.
+pub type Matrix = [f32; 16];
+
+const FIRST_PERSON_DISTANCE: f32 = 0.3;
+const THIRD_PERSON_DISTANCE: f32 = 4.0;
+const FREE_FLY_SPEED: f32 = 0.5;
+const MIN_FOV: f32 = 30.0;
+const MAX_FOV: f32 = 110.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CameraMode {
+    FirstPerson,
+    ThirdPerson,
+    FreeFly, // detached from the player
+}
+
+impl CameraMode {
+    pub fn next(self) -> CameraMode {
+        match self {
+            CameraMode::FirstPerson => CameraMode::ThirdPerson,
+            CameraMode::ThirdPerson => CameraMode::FreeFly,
+            CameraMode::FreeFly => CameraMode::FirstPerson,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Camera {
+    pub mode: CameraMode,
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+    pub x_rot: f32,
+    pub y_rot: f32,
+    pub distance: f32, // how far the eye sits behind x/y/z
+    pub fov: f32,
+    pub aspect: f32,
+    pub near: f32,
+    pub far: f32,
+    // free-fly position, interpolated like an entity's
+    xo: f32,
+    yo: f32,
+    zo: f32,
+    fly_x: f32,
+    fly_y: f32,
+    fly_z: f32,
+}
+
+impl Camera {
+    pub fn new(fov: f32, aspect: f32) -> Camera {
+        Camera {
+            mode: CameraMode::FirstPerson,
+            x: 0.0,
+            y: 0.0,
+            z: 0.0,
+            x_rot: 0.0,
+            y_rot: 0.0,
+            distance: FIRST_PERSON_DISTANCE,
+            fov,
+            aspect,
+            near: 0.05,
+            far: 1000.0,
+            xo: 0.0,
+            yo: 0.0,
+            zo: 0.0,
+            fly_x: 0.0,
+            fly_y: 0.0,
+            fly_z: 0.0,
+        }
+    }
+
+    pub fn set_mode(&mut self, mode: CameraMode) {
+        if mode == CameraMode::FreeFly {
+            // detach where the camera currently is
+            self.fly_x = self.x;
+            self.fly_y = self.y;
+            self.fly_z = self.z;
+            self.xo = self.x;
+            self.yo = self.y;
+            self.zo = self.z;
+        }
+        self.mode = mode;
+    }
+
+    pub fn set_fov(&mut self, fov: f32) {
+        self.fov = fov.clamp(MIN_FOV, MAX_FOV);
+    }
+
+    pub fn turn(&mut self, xo: f32, yo: f32) {
+        self.y_rot += xo * 0.15;
+        self.x_rot = (self.x_rot + yo * 0.15).clamp(-90.0, 90.0);
+    }
+
+    /// Moves the free-fly camera, does nothing in the other modes.
+    pub fn tick(&mut self, lwrgl: &LWRGL) {
+        if self.mode != CameraMode::FreeFly {
+            return;
+        }
+        self.xo = self.fly_x;
+        self.yo = self.fly_y;
+        self.zo = self.fly_z;
+        let mut xa = 0.0;
+        let mut ya = 0.0;
+        let mut za = 0.0;
+        if lwrgl.is_key_down(Key::Up) || lwrgl.is_key_down(Key::W) {
+            za -= 1.0;
+        }
+        if lwrgl.is_key_down(Key::Down) || lwrgl.is_key_down(Key::S) {
+            za += 1.0;
+        }
+        if lwrgl.is_key_down(Key::Left) || lwrgl.is_key_down(Key::A) {
+            xa -= 1.0;
+        }
+        if lwrgl.is_key_down(Key::Right) || lwrgl.is_key_down(Key::D) {
+            xa += 1.0;
+        }
+        if lwrgl.is_key_down(Key::Space) {
+            ya += 1.0;
+        }
+        if lwrgl.is_key_down(Key::LeftShift) {
+            ya -= 1.0;
+        }
+        let (sin, cos) = (self.y_rot * PI / 180.0).sin_cos();
+        self.fly_x += (xa * cos - za * sin) * FREE_FLY_SPEED;
+        self.fly_y += ya * FREE_FLY_SPEED;
+        self.fly_z += (za * cos + xa * sin) * FREE_FLY_SPEED;
+    }
+
+    /// Places the camera for the current mode, `entity` being the player.
+    pub fn update(&mut self, entity: &Entity, level: &Level, a: f32) {
+        match self.mode {
+            CameraMode::FirstPerson => {
+                self.follow(entity, a);
+                self.distance = FIRST_PERSON_DISTANCE;
+            }
+            CameraMode::ThirdPerson => {
+                self.follow(entity, a);
+                self.distance = self.clip_distance(level, THIRD_PERSON_DISTANCE);
+            }
+            CameraMode::FreeFly => {
+                self.x = self.xo + (self.fly_x - self.xo) * a;
+                self.y = self.yo + (self.fly_y - self.yo) * a;
+                self.z = self.zo + (self.fly_z - self.zo) * a;
+                self.distance = 0.0;
+            }
+        }
+    }
+
+    /// The camera as it would be in first person, at the eye of `entity`. Picking goes from there
+    /// whatever the mode, the view is only for rendering.
+    pub fn first_person(&self, entity: &Entity, level: &Level, a: f32) -> Camera {
+        let mut camera = self.clone();
+        camera.mode = CameraMode::FirstPerson;
+        camera.update(entity, level, a);
+        camera
+    }
+
+    fn follow(&mut self, entity: &Entity, a: f32) {
+        self.x = entity.xo + (entity.x - entity.xo) * a;
+        self.y = entity.yo + (entity.y - entity.yo) * a;
+        self.z = entity.zo + (entity.z - entity.zo) * a;
+        self.x_rot = entity.x_rot_o + (entity.x_rot - entity.x_rot_o) * a;
+        self.y_rot = entity.y_rot_o + (entity.y_rot - entity.y_rot_o) * a;
+    }
+
+    /// The unit vector the camera looks along.
+    pub fn look_dir(&self) -> [f32; 3] {
+        let (x_sin, x_cos) = (self.x_rot * PI / 180.0).sin_cos();
+        let (y_sin, y_cos) = (self.y_rot * PI / 180.0).sin_cos();
+        [y_sin * x_cos, -x_sin, -y_cos * x_cos]
+    }
+
+    /// Walks back from x/y/z towards the eye and stops short of the first solid tile.
+    fn clip_distance(&self, level: &Level, max: f32) -> f32 {
+        let (x_sin, x_cos) = (self.x_rot * PI / 180.0).sin_cos();
+        let (y_sin, y_cos) = (self.y_rot * PI / 180.0).sin_cos();
+        let xd = -y_sin * x_cos;
+        let yd = x_sin;
+        let zd = y_cos * x_cos;
+        let step = 0.1;
+        let mut distance = 0.0;
+        while distance < max {
+            let d = distance + step;
+            let x = self.x + xd * d;
+            let y = self.y + yd * d;
+            let z = self.z + zd * d;
+            if level.is_solid_tile(x.floor() as i32, y.floor() as i32, z.floor() as i32) {
+                return (distance - self.near * 2.0).max(0.0);
+            }
+            distance = d;
+        }
+        max
+    }
+
+    pub fn projection_matrix(&self) -> Matrix {
+        perspective(self.fov, self.aspect, self.near, self.far)
+    }
+
+    pub fn view_matrix(&self) -> Matrix {
+        let m = translation(0.0, 0.0, -self.distance);
+        let m = multiply(&m, &rotation_x(self.x_rot));
+        let m = multiply(&m, &rotation_y(self.y_rot));
+        multiply(&m, &translation(-self.x, -self.y, -self.z))
+    }
+
+    pub fn frustum(&self) -> Frustum {
+        Frustum::new(&self.projection_matrix(), &self.view_matrix())
+    }
+}
+
+pub fn identity() -> Matrix {
+    [
+        1.0, 0.0, 0.0, 0.0, //
+        0.0, 1.0, 0.0, 0.0, //
+        0.0, 0.0, 1.0, 0.0, //
+        0.0, 0.0, 0.0, 1.0,
+    ]
+}
+
+/// Same as `a * b`, i.e. `glLoadMatrixf(a)` followed by `glMultMatrixf(b)`.
+pub fn multiply(a: &Matrix, b: &Matrix) -> Matrix {
+    let mut m = [0.0; 16];
+    for col in 0..4 {
+        for row in 0..4 {
+            m[col * 4 + row] = (0..4).map(|k| a[k * 4 + row] * b[col * 4 + k]).sum();
+        }
+    }
+    m
+}
+
+/// Same as `gluPerspective`, with `fov` in degrees.
+pub fn perspective(fov: f32, aspect: f32, near: f32, far: f32) -> Matrix {
+    let f = 1.0 / (fov * PI / 360.0).tan();
+    let mut m = [0.0; 16];
+    m[0] = f / aspect;
+    m[5] = f;
+    m[10] = (far + near) / (near - far);
+    m[11] = -1.0;
+    m[14] = 2.0 * far * near / (near - far);
+    m
+}
+
+/// Same as `glTranslatef`.
+pub fn translation(x: f32, y: f32, z: f32) -> Matrix {
+    let mut m = identity();
+    m[12] = x;
+    m[13] = y;
+    m[14] = z;
+    m
+}
+
+/// Same as `glRotatef(angle, 1.0, 0.0, 0.0)`, with `angle` in degrees.
+pub fn rotation_x(angle: f32) -> Matrix {
+    let (sin, cos) = (angle * PI / 180.0).sin_cos();
+    let mut m = identity();
+    m[5] = cos;
+    m[6] = sin;
+    m[9] = -sin;
+    m[10] = cos;
+    m
+}
+
+/// Same as `glRotatef(angle, 0.0, 1.0, 0.0)`, with `angle` in degrees.
+pub fn rotation_y(angle: f32) -> Matrix {
+    let (sin, cos) = (angle * PI / 180.0).sin_cos();
+    let mut m = identity();
+    m[0] = cos;
+    m[2] = -sin;
+    m[8] = sin;
+    m[10] = cos;
+    m
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entity::EntityTrait;
+    use crate::level::frustrum::{BACK, BOTTOM, FRONT, LEFT, RIGHT, TOP};
+    use crate::phys::aabb::AABB;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    /// At `x`/`y`/`z` looking along -z when `y_rot` is 0, with a 90 degree square view.
+    fn camera(x: f32, y: f32, z: f32, y_rot: f32) -> Camera {
+        let mut camera = Camera::new(90.0, 1.0);
+        (camera.x, camera.y, camera.z) = (x, y, z);
+        camera.y_rot = y_rot;
+        camera.distance = 0.0;
+        camera
+    }
+
+    fn transform(m: &Matrix, [x, y, z]: [f32; 3]) -> [f32; 3] {
+        [0, 1, 2].map(|row| m[row] * x + m[4 + row] * y + m[8 + row] * z + m[12 + row])
+    }
+
+    /// Within float precision, which the far plane at 1000 blocks needs relative to its size.
+    fn assert_near(actual: &[f32], expected: &[f32]) {
+        assert!(
+            actual
+                .iter()
+                .zip(expected)
+                .all(|(a, e)| (a - e).abs() < 1e-3 * e.abs().max(1.0)),
+            "{:?} is not {:?}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn planes_match_the_projection() {
+        let camera = camera(0.0, 0.0, 0.0, 0.0);
+        let planes = camera.frustum().m_frustum;
+        let s = 0.5f32.sqrt();
+        // the sides at 45 degrees to the view, the near and far planes across it
+        assert_near(&planes[RIGHT], &[-s, 0.0, -s, 0.0]);
+        assert_near(&planes[LEFT], &[s, 0.0, -s, 0.0]);
+        assert_near(&planes[BOTTOM], &[0.0, s, -s, 0.0]);
+        assert_near(&planes[TOP], &[0.0, -s, -s, 0.0]);
+        assert_near(&planes[BACK], &[0.0, 0.0, 1.0, camera.far]);
+        assert_near(&planes[FRONT], &[0.0, 0.0, -1.0, -camera.near]);
+    }
+
+    #[test]
+    fn view_puts_what_the_camera_looks_at_ahead_of_it() {
+        let camera = camera(10.0, 5.0, 10.0, 90.0);
+        assert_near(&camera.look_dir(), &[1.0, 0.0, 0.0]);
+        let view = camera.view_matrix();
+        assert_near(&transform(&view, [20.0, 5.0, 10.0]), &[0.0, 0.0, -10.0]);
+        assert_near(&transform(&view, [10.0, 7.0, 8.0]), &[-2.0, 2.0, 0.0]);
+    }
+
+    #[test]
+    fn picks_from_the_eye_in_third_person() {
+        let level = Rc::new(RefCell::new(Level::with_dir(64, None)));
+        let mut entity = Entity::new(Rc::clone(&level));
+        entity.set_pos(3.0, 50.0, 4.0);
+        (entity.xo, entity.yo, entity.zo) = (entity.x, entity.y, entity.z);
+        let mut camera = Camera::new(70.0, 1.0);
+        camera.set_mode(CameraMode::ThirdPerson);
+        camera.update(&entity, &level.borrow(), 1.0);
+        assert_eq!(camera.distance, THIRD_PERSON_DISTANCE);
+        let eye = camera.first_person(&entity, &level.borrow(), 1.0);
+        assert_eq!((eye.x, eye.y, eye.z), (3.0, 50.0, 4.0));
+        assert_eq!(eye.distance, FIRST_PERSON_DISTANCE);
+        assert_eq!(camera.mode, CameraMode::ThirdPerson);
+    }
+
+    #[test]
+    fn culls_boxes_outside_the_view() {
+        let frustum = camera(10.0, 5.0, 10.0, 90.0).frustum();
+        let in_front = AABB::new(19.0, 4.0, 9.0, 21.0, 6.0, 11.0);
+        assert!(frustum.cube_in_frustum_aabb(&in_front));
+        assert!(frustum.cube_in_frustum(19.0, 4.0, 9.0, 21.0, 6.0, 11.0));
+        // reaching into the view from the side
+        assert!(frustum.cube_in_frustum(15.0, 4.0, 13.0, 16.0, 6.0, 20.0));
+
+        let behind = AABB::new(0.0, 4.0, 9.0, 2.0, 6.0, 11.0);
+        let beyond_far = AABB::new(1011.0, 4.0, 9.0, 1013.0, 6.0, 11.0);
+        let beside = AABB::new(15.0, 4.0, 16.0, 16.0, 6.0, 20.0);
+        for bb in [behind, beyond_far, beside] {
+            assert!(!frustum.cube_in_frustum_aabb(&bb));
+            assert!(!frustum.cube_in_frustum(bb.x0, bb.y0, bb.z0, bb.x1, bb.y1, bb.z1));
+        }
+    }
+}
@@ -0,0 +1,204 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use serde::Deserialize;
+
+use super::model::Model;
+
+/// The rotation of a part at some tick of a clip.
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct Keyframe {
+    pub time: f32,
+    pub rot: [f32; 3], // around x, y and z in degrees
+}
+
+/// A named animation in a model file, turning its parts from keyframe to keyframe.
+#[derive(Debug, Deserialize)]
+pub struct Clip {
+    pub length: f32, // in ticks
+    #[serde(default, rename = "loop")]
+    pub looping: bool,
+    pub parts: HashMap<String, Vec<Keyframe>>, // keyframes by part, sorted by time
+}
+
+impl Clip {
+    /// The rotation in radians of a part with `keyframes` at `time`, in between two keyframes
+    /// linearly.
+    fn sample(&self, keyframes: &[Keyframe], time: f32) -> [f32; 3] {
+        let time = if self.looping {
+            time.rem_euclid(self.length)
+        } else {
+            time.min(self.length)
+        };
+        let next = keyframes.iter().position(|key| key.time > time);
+        let rot = match next {
+            Some(0) => keyframes[0].rot,
+            Some(i) => {
+                let (k0, k1) = (keyframes[i - 1], keyframes[i]);
+                let f = (time - k0.time) / (k1.time - k0.time);
+                [0, 1, 2].map(|axis| k0.rot[axis] + (k1.rot[axis] - k0.rot[axis]) * f)
+            }
+            None => keyframes.last().map_or([0.0; 3], |key| key.rot),
+        };
+        rot.map(f32::to_radians)
+    }
+}
+
+/// A clip being played, with how much it counts towards the pose.
+struct Layer {
+    name: String,
+    clip: Rc<Clip>,
+    time: f32,
+    time_o: f32,
+    weight: f32,
+    weight_o: f32,
+}
+
+/// Plays a model's clips on top of each other, advancing them every tick. Each clip blends its
+/// parts' rotations over the pose of the clips before it by its weight.
+pub struct Animator {
+    layers: Vec<Layer>,
+}
+
+impl Animator {
+    /// Layers the named clips of `model` in order, those the model does not have are left out.
+    /// They start at weight 0, `time` ticks in.
+    pub fn new(model: &Model, names: &[&str], time: f32) -> Animator {
+        let layers = names
+            .iter()
+            .filter_map(|name| {
+                Some(Layer {
+                    name: name.to_string(),
+                    clip: model.clip(name)?,
+                    time,
+                    time_o: time,
+                    weight: 0.0,
+                    weight_o: 0.0,
+                })
+            })
+            .collect();
+        Animator { layers }
+    }
+
+    fn layer_mut(&mut self, name: &str) -> Option<&mut Layer> {
+        self.layers.iter_mut().find(|layer| layer.name == name)
+    }
+
+    pub fn set_weight(&mut self, name: &str, weight: f32) {
+        if let Some(layer) = self.layer_mut(name) {
+            layer.weight = weight.clamp(0.0, 1.0);
+        }
+    }
+
+    /// Plays the clip from its start at full weight, until it ends if it does not loop.
+    pub fn play(&mut self, name: &str) {
+        if let Some(layer) = self.layer_mut(name) {
+            layer.time = 0.0;
+            layer.time_o = 0.0;
+            layer.weight = 1.0;
+        }
+    }
+
+    pub fn tick(&mut self) {
+        for layer in &mut self.layers {
+            layer.time_o = layer.time;
+            layer.weight_o = layer.weight;
+            layer.time += 1.0;
+            if !layer.clip.looping && layer.time >= layer.clip.length {
+                layer.weight = 0.0;
+            }
+        }
+    }
+
+    /// How far into the clip it is and how much it counts, `a` of the way into the tick.
+    pub fn state(&self, name: &str, a: f32) -> Option<(f32, f32)> {
+        let layer = self.layers.iter().find(|layer| layer.name == name)?;
+        Some((
+            layer.time_o + (layer.time - layer.time_o) * a,
+            layer.weight_o + (layer.weight - layer.weight_o) * a,
+        ))
+    }
+
+    /// Poses `model` as it is `a` of the way into the tick. Parts none of the clips turn are left
+    /// as they are.
+    pub fn apply(&self, model: &mut Model, a: f32) {
+        let mut pose: HashMap<&str, [f32; 3]> = HashMap::new();
+        for layer in &self.layers {
+            let (time, weight) = self.state(&layer.name, a).unwrap();
+            for (part, keyframes) in &layer.clip.parts {
+                let rot = layer.clip.sample(keyframes, time);
+                let current = pose.entry(part).or_insert([0.0; 3]);
+                for axis in 0..3 {
+                    current[axis] += (rot[axis] - current[axis]) * weight;
+                }
+            }
+        }
+        for (part, [x_rot, y_rot, z_rot]) in pose {
+            if let Some(cube) = model.part_mut(part) {
+                cube.x_rot = x_rot;
+                cube.y_rot = y_rot;
+                cube.z_rot = z_rot;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn model() -> Model {
+        Model::parse(
+            r#"{"texture": "char.png", "texture_width": 64, "texture_height": 32,
+            "parts": [{"name": "arm", "uv": [0, 0], "boxes": []}],
+            "animations": {
+                "swing": {"length": 4, "loop": true, "parts": {"arm": [
+                    {"time": 0, "rot": [0, 0, 0]},
+                    {"time": 2, "rot": [90, 0, 0]},
+                    {"time": 4, "rot": [0, 0, 0]}
+                ]}},
+                "raise": {"length": 2, "parts": {"arm": [
+                    {"time": 0, "rot": [0, 0, 90]},
+                    {"time": 2, "rot": [0, 0, 90]}
+                ]}}
+            }}"#,
+        )
+        .unwrap()
+    }
+
+    fn arm_rot(model: &mut Model) -> [f32; 3] {
+        let arm = model.part_mut("arm").unwrap();
+        [arm.x_rot, arm.y_rot, arm.z_rot].map(f32::to_degrees)
+    }
+
+    #[test]
+    fn loops_in_between_keyframes_and_ticks() {
+        let mut model = model();
+        let mut animator = Animator::new(&model, &["swing", "walk"], 0.0);
+        animator.set_weight("swing", 1.0);
+        animator.tick();
+        animator.apply(&mut model, 0.5);
+        assert!((arm_rot(&mut model)[0] - 22.5).abs() < 0.01);
+        for _ in 0..4 {
+            animator.tick();
+        }
+        animator.apply(&mut model, 1.0);
+        assert!((arm_rot(&mut model)[0] - 45.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn blends_one_shot_clips_over_the_ones_before() {
+        let mut model = model();
+        let mut animator = Animator::new(&model, &["swing", "raise"], 2.0);
+        animator.set_weight("swing", 1.0);
+        animator.tick();
+        animator.play("raise");
+        animator.set_weight("raise", 0.5);
+        animator.apply(&mut model, 1.0);
+        let [x, _, z] = arm_rot(&mut model);
+        assert!((x - 22.5).abs() < 0.01 && (z - 45.0).abs() < 0.01);
+        animator.tick();
+        animator.tick();
+        assert_eq!(animator.state("raise", 1.0).unwrap().1, 0.0);
+    }
+}
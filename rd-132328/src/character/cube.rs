@@ -1,14 +1,39 @@
+use std::cell::OnceCell;
+use std::rc::Rc;
+
 use lwrgl::glu_sys::{
-    glBegin, glEnd, glPopMatrix, glPushMatrix, glRotatef, glTranslatef, GL_QUADS,
+    glBegin, glCallList, glDeleteLists, glEnd, glEndList, glGenLists, glNewList, glPopMatrix,
+    glPushMatrix, glRotatef, glTranslatef, GLuint, GL_COMPILE, GL_QUADS,
 };
 
 use super::{polygon::Polygon, vertex::Vertex};
 
+/// A display list with a part's polygons, freed along with the last copy of the part.
+///
+/// Display lists are how GL 1.1 keeps geometry on the driver's side. Buffer objects and instanced
+/// drawing came later and are not in the bindings, so every part is drawn with its own transform
+/// and a call of the list it shares with its copies.
+struct DisplayList(GLuint);
+
+impl Drop for DisplayList {
+    fn drop(&mut self) {
+        unsafe {
+            glDeleteLists(self.0, 1);
+        }
+    }
+}
+
+/// A part of a model. Copies of it share its polygons and the display list they are compiled
+/// into, only its position and rotation are its own.
+#[derive(Clone)]
 pub struct Cube {
     vertices: Vec<Vertex>,
-    polygons: Vec<Polygon>,
+    polygons: Rc<Vec<Polygon>>,
+    list: Rc<OnceCell<DisplayList>>, // compiled on first render
     x_tex_offs: i32,
     y_tex_offs: i32,
+    tex_width: i32,
+    tex_height: i32,
     pub x: f32,
     pub y: f32,
     pub z: f32,
@@ -18,12 +43,17 @@ pub struct Cube {
 }
 
 impl Cube {
-    pub fn new(x_tex_offs: i32, y_tex_offs: i32) -> Cube {
+    /// A part textured from a `tex_width` by `tex_height` texture, its boxes' faces laid out from
+    /// `x_tex_offs`/`y_tex_offs` on.
+    pub fn new(x_tex_offs: i32, y_tex_offs: i32, tex_width: i32, tex_height: i32) -> Cube {
         Cube {
             vertices: vec![],
-            polygons: vec![],
+            polygons: Rc::default(),
+            list: Rc::default(),
             x_tex_offs,
             y_tex_offs,
+            tex_width,
+            tex_height,
             x: 0.0,
             y: 0.0,
             z: 0.0,
@@ -33,9 +63,17 @@ impl Cube {
         }
     }
 
+    /// Moves where the faces of the boxes added next are laid out on the texture.
+    pub fn tex_offs(&mut self, x_tex_offs: i32, y_tex_offs: i32) {
+        self.x_tex_offs = x_tex_offs;
+        self.y_tex_offs = y_tex_offs;
+    }
+
+    /// Adds a `w` by `h` by `d` box with its corner at `x0`/`y0`/`z0` relative to the pivot.
     pub fn add_box(&mut self, x0: f32, y0: f32, z0: f32, w: i32, h: i32, d: i32) {
-        self.vertices = Vec::with_capacity(8);
-        self.polygons = Vec::with_capacity(6);
+        let tex_size = (self.tex_width, self.tex_height);
+        let polygons = Rc::make_mut(&mut self.polygons);
+        self.list = Rc::default();
         let x1 = x0 + w as f32;
         let y1 = y0 + h as f32;
         let z1 = z0 + d as f32;
@@ -55,47 +93,65 @@ impl Cube {
         self.vertices.push(l1.clone());
         self.vertices.push(l2.clone());
         self.vertices.push(l3.clone());
-        self.polygons.push(Polygon::from_uvs(
+        polygons.push(Polygon::from_uvs(
             vec![l1.clone(), u1.clone(), u2.clone(), l2.clone()],
-            self.x_tex_offs + d + w,
-            self.y_tex_offs + d,
-            self.x_tex_offs + d + w + d,
-            self.y_tex_offs + d + h,
+            (
+                self.x_tex_offs + d + w,
+                self.y_tex_offs + d,
+                self.x_tex_offs + d + w + d,
+                self.y_tex_offs + d + h,
+            ),
+            tex_size,
         ));
-        self.polygons.push(Polygon::from_uvs(
+        polygons.push(Polygon::from_uvs(
             vec![u0.clone(), l0.clone(), l3.clone(), u3.clone()],
-            self.x_tex_offs + 0,
-            self.y_tex_offs + d,
-            self.x_tex_offs + d,
-            self.y_tex_offs + d + h,
+            (
+                self.x_tex_offs + 0,
+                self.y_tex_offs + d,
+                self.x_tex_offs + d,
+                self.y_tex_offs + d + h,
+            ),
+            tex_size,
         ));
-        self.polygons.push(Polygon::from_uvs(
+        polygons.push(Polygon::from_uvs(
             vec![l1.clone(), l0.clone(), u0.clone(), u1.clone()],
-            self.x_tex_offs + d,
-            self.y_tex_offs + 0,
-            self.x_tex_offs + d + w,
-            self.y_tex_offs + d,
+            (
+                self.x_tex_offs + d,
+                self.y_tex_offs + 0,
+                self.x_tex_offs + d + w,
+                self.y_tex_offs + d,
+            ),
+            tex_size,
         ));
-        self.polygons.push(Polygon::from_uvs(
+        polygons.push(Polygon::from_uvs(
             vec![u2.clone(), u3.clone(), l3.clone(), l2.clone()],
-            self.x_tex_offs + d + w,
-            self.y_tex_offs + 0,
-            self.x_tex_offs + d + w + w,
-            self.y_tex_offs + d,
+            (
+                self.x_tex_offs + d + w,
+                self.y_tex_offs + 0,
+                self.x_tex_offs + d + w + w,
+                self.y_tex_offs + d,
+            ),
+            tex_size,
         ));
-        self.polygons.push(Polygon::from_uvs(
+        polygons.push(Polygon::from_uvs(
             vec![u1.clone(), u0.clone(), u3.clone(), u2.clone()],
-            self.x_tex_offs + d,
-            self.y_tex_offs + d,
-            self.x_tex_offs + d + w,
-            self.y_tex_offs + d + h,
+            (
+                self.x_tex_offs + d,
+                self.y_tex_offs + d,
+                self.x_tex_offs + d + w,
+                self.y_tex_offs + d + h,
+            ),
+            tex_size,
         ));
-        self.polygons.push(Polygon::from_uvs(
+        polygons.push(Polygon::from_uvs(
             vec![l0.clone(), l1.clone(), l2.clone(), l3.clone()],
-            self.x_tex_offs + d + w + d,
-            self.y_tex_offs + d,
-            self.x_tex_offs + d + w + d + w,
-            self.y_tex_offs + d + h,
+            (
+                self.x_tex_offs + d + w + d,
+                self.y_tex_offs + d,
+                self.x_tex_offs + d + w + d + w,
+                self.y_tex_offs + d + h,
+            ),
+            tex_size,
         ));
     }
 
@@ -105,6 +161,11 @@ impl Cube {
         self.z = z;
     }
 
+    #[cfg(test)]
+    pub fn shares_list_with(&self, other: &Cube) -> bool {
+        Rc::ptr_eq(&self.list, &other.list)
+    }
+
     pub unsafe fn render(&self) {
         let c = 57.29578 as f32;
         glPushMatrix();
@@ -112,11 +173,18 @@ impl Cube {
         glRotatef(self.z_rot * c, 0.0, 0.0, 1.0);
         glRotatef(self.y_rot * c, 0.0, 1.0, 0.0);
         glRotatef(self.x_rot * c, 1.0, 0.0, 0.0);
-        glBegin(GL_QUADS);
-        for polygon in &self.polygons {
-            polygon.render();
-        }
-        glEnd();
+        let list = self.list.get_or_init(|| {
+            let list = DisplayList(glGenLists(1));
+            glNewList(list.0, GL_COMPILE);
+            glBegin(GL_QUADS);
+            for polygon in self.polygons.iter() {
+                polygon.render();
+            }
+            glEnd();
+            glEndList();
+            list
+        });
+        glCallList(list.0);
         glPopMatrix();
     }
 }
@@ -1,4 +1,6 @@
+pub mod animation;
 mod cube;
+pub mod model;
 mod polygon;
 mod vec3;
 mod vertex;
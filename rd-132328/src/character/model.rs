@@ -0,0 +1,149 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs;
+use std::rc::Rc;
+
+use serde::Deserialize;
+
+use super::{animation::Clip, cube::Cube};
+
+/// A model as written in its JSON file. Positions are in model pixels, 16 to a block, with y
+/// pointing down.
+#[derive(Deserialize)]
+struct ModelFile {
+    texture: String,
+    texture_width: i32,
+    texture_height: i32,
+    parts: Vec<PartFile>,
+    #[serde(default)]
+    animations: HashMap<String, Clip>,
+}
+
+/// A part turns around its pivot, taking its boxes along.
+#[derive(Deserialize)]
+struct PartFile {
+    name: String,
+    #[serde(default)]
+    pivot: [f32; 3],
+    uv: [i32; 2],
+    boxes: Vec<BoxFile>,
+}
+
+#[derive(Deserialize)]
+struct BoxFile {
+    origin: [f32; 3], // relative to the pivot
+    size: [i32; 3],
+    uv: Option<[i32; 2]>, // when not laid out from the part's uv
+}
+
+thread_local! {
+    // loaded models by path, each load hands out a copy sharing the geometry
+    static MODELS: RefCell<HashMap<String, Model>> = RefCell::new(HashMap::new());
+}
+
+/// An entity's shape, made of named parts that can be turned separately.
+#[derive(Clone)]
+pub struct Model {
+    pub texture: String,
+    parts: Vec<(String, Cube)>,
+    clips: HashMap<String, Rc<Clip>>,
+}
+
+impl Model {
+    /// Loads the model from the JSON file at `path`, reading the file only the first time.
+    /// Panics naming the file if it cannot be read or is not a model.
+    pub fn load(path: &str) -> Model {
+        MODELS.with(|models| {
+            models
+                .borrow_mut()
+                .entry(path.to_string())
+                .or_insert_with(|| {
+                    let json = fs::read_to_string(path)
+                        .unwrap_or_else(|e| panic!("Could not read the model {}: {}", path, e));
+                    Model::parse(&json).unwrap_or_else(|e| panic!("Broken model {}: {}", path, e))
+                })
+                .clone()
+        })
+    }
+
+    pub fn parse(json: &str) -> serde_json::Result<Model> {
+        let file: ModelFile = serde_json::from_str(json)?;
+        let parts = file
+            .parts
+            .into_iter()
+            .map(|part| {
+                let [u, v] = part.uv;
+                let mut cube = Cube::new(u, v, file.texture_width, file.texture_height);
+                let [x, y, z] = part.pivot;
+                cube.set_pos(x, y, z);
+                for b in part.boxes {
+                    let [u, v] = b.uv.unwrap_or(part.uv);
+                    cube.tex_offs(u, v);
+                    let [x0, y0, z0] = b.origin;
+                    let [w, h, d] = b.size;
+                    cube.add_box(x0, y0, z0, w, h, d);
+                }
+                (part.name, cube)
+            })
+            .collect();
+        let clips = file
+            .animations
+            .into_iter()
+            .map(|(name, clip)| (name, Rc::new(clip)))
+            .collect();
+        Ok(Model {
+            texture: file.texture,
+            parts,
+            clips,
+        })
+    }
+
+    pub fn part_mut(&mut self, name: &str) -> Option<&mut Cube> {
+        self.parts
+            .iter_mut()
+            .find(|(part, _)| part == name)
+            .map(|(_, cube)| cube)
+    }
+
+    pub fn clip(&self, name: &str) -> Option<Rc<Clip>> {
+        self.clips.get(name).cloned()
+    }
+
+    pub unsafe fn render(&self) {
+        for (_, cube) in &self.parts {
+            cube.render();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_parts_with_their_pivots() {
+        let mut model = Model::load("zombie.json");
+        assert_eq!(model.texture, "char.png");
+        let arm = model.part_mut("arm1").unwrap();
+        assert_eq!((arm.x, arm.y, arm.z), (5.0, 2.0, 0.0));
+        assert!(model.part_mut("tail").is_none());
+    }
+
+    #[test]
+    fn copies_share_the_geometry_but_not_the_pose() {
+        let mut a = Model::load("zombie.json");
+        let b = Model::load("zombie.json");
+        for ((_, a), (_, b)) in a.parts.iter().zip(&b.parts) {
+            assert!(a.shares_list_with(b));
+        }
+        a.part_mut("head").unwrap().x_rot = 1.0;
+        assert_eq!(b.parts[0].1.x_rot, 0.0);
+    }
+
+    #[test]
+    fn rejects_parts_without_boxes() {
+        let json = r#"{"texture": "char.png", "texture_width": 64, "texture_height": 32,
+            "parts": [{"name": "head", "uv": [0, 0]}]}"#;
+        assert!(Model::parse(json).is_err());
+    }
+}
@@ -1,23 +1,34 @@
-use lwrgl::glu_sys::{glColor3f, glTexCoord2f, glVertex3f};
+use lwrgl::glu_sys::{glTexCoord2f, glVertex3f};
 
 use super::vertex::Vertex;
 
+#[derive(Clone)]
 pub struct Polygon {
     pub vertices: Vec<Vertex>,
     pub vertex_count: i32,
+    tex_width: f32,
+    tex_height: f32,
 }
 
 impl Polygon {
-    pub fn new(vertices: Vec<Vertex>) -> Polygon {
+    /// A polygon textured from a `tex_width` by `tex_height` texture, its vertices' u and v being
+    /// in texture pixels.
+    pub fn new(vertices: Vec<Vertex>, tex_width: i32, tex_height: i32) -> Polygon {
         let vertex_count = vertices.len() as i32;
         Polygon {
             vertices,
             vertex_count,
+            tex_width: tex_width as f32,
+            tex_height: tex_height as f32,
         }
     }
 
-    pub fn from_uvs(vertices: Vec<Vertex>, u0: i32, v0: i32, u1: i32, v1: i32) -> Polygon {
-        let mut p = Polygon::new(vertices);
+    pub fn from_uvs(
+        vertices: Vec<Vertex>,
+        (u0, v0, u1, v1): (i32, i32, i32, i32),
+        (tex_width, tex_height): (i32, i32),
+    ) -> Polygon {
+        let mut p = Polygon::new(vertices, tex_width, tex_height);
         p.vertices[0] = p.vertices[0].remap(u1 as f32, v0 as f32);
         p.vertices[1] = p.vertices[1].remap(u0 as f32, v0 as f32);
         p.vertices[2] = p.vertices[2].remap(u0 as f32, v1 as f32);
@@ -26,11 +37,10 @@ impl Polygon {
     }
 
     pub unsafe fn render(&self) {
-        glColor3f(1.0, 1.0, 1.0);
         let mut i = 3;
         while i >= 0 {
             let v = &self.vertices[i as usize];
-            glTexCoord2f(v.u / 64.0, v.v / 32.0);
+            glTexCoord2f(v.u / self.tex_width, v.v / self.tex_height);
             glVertex3f(v.pos.x, v.pos.y, v.pos.z);
             i -= 1;
         }
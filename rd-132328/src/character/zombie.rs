@@ -1,31 +1,48 @@
-use std::{cell::RefCell, f64::consts::PI, rc::Rc, time::Instant};
+use std::{any::Any, cell::RefCell, f64::consts::PI, rc::Rc};
 
-use lwrgl::glu_sys::{
-    glBindTexture, glDisable, glEnable, glPopMatrix, glPushMatrix, glRotatef, glScalef,
-    glTranslatef, GLuint, GL_NEAREST, GL_TEXTURE_2D,
-};
+use lwrgl::glu_sys::{glColor3f, glPopMatrix, glPushMatrix, glRotatef, glScalef, glTranslatef};
 
 use crate::{
-    entity::{Entity, EntityTrait},
-    level::level::Level,
-    textures::load_texture,
-    timer::PROGRAM_START,
+    ai::{
+        goal::Goals,
+        goals::{self, ChaseGoal, FleeGoal, IdleGoal, LookAtPlayerGoal, WanderGoal},
+        path::Walker,
+    },
+    entity::{wrap_degrees, Entity, EntityTrait, AIR_DRAG, GRAVITY, JUMP_SPEED},
+    level::{level::Level, tesselator::Tesselator},
+    saved_entity::SavedEntity,
 };
 
-use super::cube::Cube;
+use super::{animation::Animator, model::Model};
+
+/// Zombies go after the player within this many blocks and wander around otherwise.
+const CHASE_RADIUS: f32 = 16.0;
+const LOOK_RADIUS: f32 = 8.0;
+/// Highest drop zombies are willing to take.
+const MAX_DROP: i32 = 3;
+const ATTACK_DAMAGE: i32 = 2;
+/// Ticks between two hits on the player.
+const ATTACK_TICKS: i32 = 60;
+/// Ticks a dead zombie takes to fall over before it is gone.
+const DEATH_TICKS: i32 = 20;
+/// Distance moved in a tick at which the walk animation fully takes over from standing idle.
+const WALK_ANIMATION_SPEED: f32 = 0.05;
+/// Degrees the body turns at most in a tick towards where the zombie walks.
+const BODY_TURN_SPEED: f32 = 20.0;
+/// Degrees the head turns at most to either side of the body.
+const MAX_HEAD_YAW: f32 = 70.0;
 
 pub struct Zombie {
     pub entity: Entity,
-    pub head: Cube,
-    pub body: Cube,
-    pub arm0: Cube,
-    pub arm1: Cube,
-    pub leg0: Cube,
-    pub leg1: Cube,
-    pub rot: f32,
-    pub time_offs: f32,
-    pub speed: f32,
-    pub rot_a: f32,
+    pub model: Model,
+    pub animator: Animator,
+    // the body turns with entity.y_rot, the head looks around on its own
+    pub head_y_rot: f32,
+    pub head_y_rot_o: f32,
+    goals: Goals,
+    looking: bool,     // at something, instead of bobbing the head around
+    attack_ticks: i32, // until it can hit the player again
+    death_time: i32,   // ticks since it died
 }
 
 impl Zombie {
@@ -34,89 +51,165 @@ impl Zombie {
         entity.x = x;
         entity.y = y;
         entity.z = z;
+        entity.y_rot = rand::random::<f32>() * 360.0;
+        entity.y_rot_o = entity.y_rot;
+        let head_y_rot = entity.y_rot;
 
-        let mut head = Cube::new(0, 0);
-        head.add_box(-4.0, -8.0, -4.0, 8, 8, 8);
-
-        let mut body = Cube::new(16, 16);
-        body.add_box(-4.0, 0.0, -2.0, 8, 12, 4);
-
-        let mut arm0 = Cube::new(40, 16);
-        arm0.add_box(-3.0, -2.0, -2.0, 4, 12, 4);
-        arm0.set_pos(-5.0, 2.0, 0.0);
-
-        let mut arm1 = Cube::new(40, 16);
-        arm1.add_box(-1.0, -2.0, -2.0, 4, 12, 4);
-        arm1.set_pos(5.0, 2.0, 0.0);
-
-        let mut leg0 = Cube::new(0, 16);
-        leg0.add_box(-2.0, 0.0, -2.0, 4, 12, 4);
-        leg0.set_pos(-2.0, 12.0, 0.0);
-
-        let mut leg1 = Cube::new(0, 16);
-        leg1.add_box(-2.0, 0.0, -2.0, 4, 12, 4);
-        leg1.set_pos(2.0, 12.0, 0.0);
+        let model = Model::load("zombie.json");
+        // not all in step with each other
+        let mut animator = Animator::new(
+            &model,
+            &["idle", "walk", "attack"],
+            rand::random::<f32>() * 1000.0,
+        );
+        animator.set_weight("idle", 1.0);
 
         Zombie {
             entity,
-            head,
-            body,
-            arm0,
-            arm1,
-            leg0,
-            leg1,
-            rot: (rand::random::<f64>() * PI * 2.0) as f32,
-            time_offs: rand::random::<f64>() as f32 * 1239813.0,
-            speed: 1.0,
-            rot_a: (rand::random::<f64>() + 1.0) as f32 * 0.01,
+            model,
+            animator,
+            head_y_rot,
+            head_y_rot_o: head_y_rot,
+            goals: Goals::new()
+                .add(FleeGoal::new(goals::nearby_lava))
+                .add(ChaseGoal::new(
+                    Walker::new(1.8, JUMP_SPEED, 0.5, MAX_DROP),
+                    CHASE_RADIUS,
+                ))
+                .add(IdleGoal::new())
+                .add(WanderGoal::new())
+                .add(LookAtPlayerGoal::new(LOOK_RADIUS)),
+            looking: false,
+            attack_ticks: 0,
+            death_time: 0,
         }
     }
 
-    pub unsafe fn render(&mut self, a: f32) {
-        glEnable(GL_TEXTURE_2D);
-        glBindTexture(
-            GL_TEXTURE_2D,
-            load_texture("char.png", GL_NEAREST as i32) as GLuint,
-        );
-        glPushMatrix();
-        let time = Instant::now().duration_since(*PROGRAM_START).as_nanos() as f64 / 1.0E9
-            * 10.0
-            * self.speed as f64
-            + self.time_offs as f64;
+    /// Picks up where the zombie saved as `saved` left off.
+    pub fn load(&mut self, saved: &SavedEntity) {
+        if let SavedEntity::Zombie {
+            entity,
+            head_y_rot,
+            attack_ticks,
+            death_time,
+            wander_rot,
+            wander_rot_a,
+        } = saved
+        {
+            self.entity.load(entity);
+            self.head_y_rot = *head_y_rot;
+            self.head_y_rot_o = *head_y_rot;
+            self.attack_ticks = *attack_ticks;
+            self.death_time = *death_time;
+            if let Some(wander) = self.goals.get_mut::<WanderGoal>() {
+                wander.rot = *wander_rot;
+                wander.rot_a = *wander_rot_a;
+            }
+        }
+    }
 
-        let size = 0.058333334;
-        let yy = (-((time * 0.6662).sin().abs()) * 5.0 - 23.0) as f32;
+    /// Thinks, moves and attacks for a tick, none of which needs the window.
+    fn step(&mut self) {
         let this = &mut self.entity;
-        glTranslatef(
-            this.xo + (this.x - this.xo) * a,
-            this.yo + (this.y - this.yo) * a,
-            this.zo + (this.z - this.zo) * a,
-        );
-        glScalef(1.0, -1.0, 1.0);
-        glScalef(size, size, size);
-        glTranslatef(0.0, yy, 0.0);
-        let c = 57.29578 as f32;
-        glRotatef(self.rot * c + 180.0, 0.0, 1.0, 0.0);
-        self.head.y_rot = (time * 0.83).sin() as f32 * 1.0;
-        self.head.x_rot = time.sin() as f32 * 0.8;
-        self.arm0.x_rot = (time * 0.6662 + PI).sin() as f32 * 2.0;
-        self.arm0.z_rot = ((time * 0.2312).sin() + 1.0) as f32 * 1.0;
-        self.arm1.x_rot = (time * 0.6662).sin() as f32 * 2.0;
-        self.arm1.z_rot = ((time * 0.2812).sin() - 1.0) as f32 * 1.0;
-        self.leg0.x_rot = (time * 0.6662).sin() as f32 * 1.4;
-        self.leg1.x_rot = (time * 0.6662 + PI).sin() as f32 * 1.4;
-        self.head.render();
-        self.body.render();
-        self.arm0.render();
-        self.arm1.render();
-        self.leg0.render();
-        self.leg1.render();
-        glPopMatrix();
-        glDisable(GL_TEXTURE_2D);
+        this.xo = this.x;
+        this.yo = this.y;
+        this.zo = this.z;
+        this.y_rot_o = this.y_rot;
+        this.x_rot_o = this.x_rot;
+        self.head_y_rot_o = self.head_y_rot;
+        if this.hurt_time > 0 {
+            this.hurt_time -= 1;
+        }
+        if self.attack_ticks > 0 {
+            self.attack_ticks -= 1;
+        }
+        let intent = if this.is_dead() {
+            self.death_time += 1;
+            Default::default()
+        } else {
+            self.goals.tick(this)
+        };
+        if let Some(rot) = intent.walk {
+            let speed = if this.on_ground { 0.02 } else { 0.005 };
+            this.xd += rot.sin() * speed;
+            this.zd += rot.cos() * speed;
+            let turn = wrap_degrees(180.0 - rot.to_degrees() - this.y_rot);
+            this.y_rot += turn.clamp(-BODY_TURN_SPEED, BODY_TURN_SPEED);
+        }
+        if this.on_ground && intent.jump {
+            this.yd = JUMP_SPEED;
+        }
+        let (head_y_rot, x_rot) = match intent.look_at {
+            Some([x, y, z]) => {
+                let xd = x - this.x;
+                let yd = y - (this.y + 1.62);
+                let zd = z - this.z;
+                (
+                    180.0 - xd.atan2(zd).to_degrees(),
+                    -yd.atan2((xd * xd + zd * zd).sqrt()).to_degrees(),
+                )
+            }
+            None => (this.y_rot, 0.0),
+        };
+        let head_yaw = wrap_degrees(head_y_rot - this.y_rot).clamp(-MAX_HEAD_YAW, MAX_HEAD_YAW);
+        self.head_y_rot += wrap_degrees(this.y_rot + head_yaw - self.head_y_rot);
+        this.x_rot = x_rot;
+        self.looking = intent.look_at.is_some();
+        this.yd -= GRAVITY;
+        this.move_(this.xd, this.yd, this.zd);
+        this.xd *= 0.91;
+        this.yd *= AIR_DRAG;
+        this.zd *= 0.91;
+        if this.y > 100.0 {
+            this.reset_pos();
+        }
+        if this.on_ground {
+            this.xd *= 0.8;
+            this.zd *= 0.8;
+        }
+        let xd = this.x - this.xo;
+        let zd = this.z - this.zo;
+        self.animator
+            .set_weight("walk", (xd * xd + zd * zd).sqrt() / WALK_ANIMATION_SPEED);
+        self.animator.tick();
+        self.attack();
+    }
+
+    /// Hits the player when touching them.
+    fn attack(&mut self) {
+        let this = &self.entity;
+        if this.is_dead() || self.attack_ticks > 0 {
+            return;
+        }
+        let mut level = this.level.borrow_mut();
+        if level
+            .player_bb
+            .is_some_and(|player| player.intersects(&this.bb.grow(0.2, 0.0, 0.2)))
+        {
+            level.hurt_player(ATTACK_DAMAGE, this.x, this.z);
+            self.attack_ticks = ATTACK_TICKS;
+            self.animator.play("attack");
+        }
     }
 }
 
 impl EntityTrait for Zombie {
+    fn entity(&self) -> &Entity {
+        &self.entity
+    }
+
+    fn entity_mut(&mut self) -> &mut Entity {
+        &mut self.entity
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
     fn reset_pos(&mut self) {
         self.entity.reset_pos();
     }
@@ -130,35 +223,7 @@ impl EntityTrait for Zombie {
     }
 
     fn tick(&mut self, _lwrgl: &lwrgl::LWRGL) {
-        let this = &mut self.entity;
-        this.xo = this.x;
-        this.yo = this.y;
-        this.zo = this.z;
-        self.rot += self.rot_a;
-        self.rot_a = (self.rot_a as f64 * 0.99) as f32;
-        self.rot_a = (self.rot_a as f64
-            + (rand::random::<f64>() - rand::random::<f64>())
-                * rand::random::<f64>()
-                * rand::random::<f64>()
-                * 0.01) as f32;
-        let xa = self.rot.sin();
-        let ya = self.rot.cos();
-        if this.on_ground && rand::random::<f64>() < 0.01 {
-            this.yd = 0.12;
-        }
-        this.move_relative(xa, ya, if this.on_ground { 0.02 } else { 0.005 });
-        this.yd = (this.yd as f64 - 0.005) as f32;
-        this.move_(this.xd, this.yd, this.zd);
-        this.xd *= 0.91;
-        this.yd *= 0.98;
-        this.zd *= 0.91;
-        if this.y > 100.0 {
-            this.reset_pos();
-        }
-        if this.on_ground {
-            this.xd *= 0.8;
-            this.zd *= 0.8;
-        }
+        self.step();
     }
 
     fn move_(&mut self, xa: f32, ya: f32, za: f32) {
@@ -168,4 +233,127 @@ impl EntityTrait for Zombie {
     fn move_relative(&mut self, xa: f32, za: f32, speed: f32) {
         self.entity.move_relative(xa, za, speed);
     }
+
+    fn is_pushable(&self) -> bool {
+        true
+    }
+
+    fn is_attackable(&self) -> bool {
+        !self.entity.is_dead()
+    }
+
+    fn is_removed(&self) -> bool {
+        self.death_time >= DEATH_TICKS
+    }
+
+    fn save(&self) -> Option<SavedEntity> {
+        let wander = self.goals.get::<WanderGoal>().unwrap();
+        Some(SavedEntity::Zombie {
+            entity: self.entity.data(),
+            head_y_rot: self.head_y_rot,
+            attack_ticks: self.attack_ticks,
+            death_time: self.death_time,
+            wander_rot: wander.rot,
+            wander_rot_a: wander.rot_a,
+        })
+    }
+
+    fn texture(&self) -> Option<&str> {
+        Some(&self.model.texture)
+    }
+
+    unsafe fn render(&mut self, _t: &mut Tesselator, a: f32) {
+        glPushMatrix();
+        let size = 0.058333334;
+        // bobs up and down with every step
+        let bob = match (self.animator.state("walk", a), self.model.clip("walk")) {
+            (Some((time, weight)), Some(walk)) => {
+                (time / walk.length * PI as f32 * 2.0).sin().abs() * weight
+            }
+            _ => 0.0,
+        };
+        let yy = -bob * 5.0 - 23.0;
+        let this = &mut self.entity;
+        glTranslatef(
+            this.xo + (this.x - this.xo) * a,
+            this.yo + (this.y - this.yo) * a,
+            this.zo + (this.z - this.zo) * a,
+        );
+        // tips over when dead
+        glRotatef(
+            self.death_time as f32 / DEATH_TICKS as f32 * 90.0,
+            0.0,
+            0.0,
+            1.0,
+        );
+        glScalef(1.0, -1.0, 1.0);
+        glScalef(size, size, size);
+        glTranslatef(0.0, yy, 0.0);
+        let y_rot = this.y_rot_o + (this.y_rot - this.y_rot_o) * a;
+        let head_y_rot = self.head_y_rot_o + (self.head_y_rot - self.head_y_rot_o) * a;
+        let x_rot = this.x_rot_o + (this.x_rot - this.x_rot_o) * a;
+        glRotatef(-y_rot, 0.0, 1.0, 0.0);
+        self.animator.apply(&mut self.model, a);
+        if let Some(head) = self.model.part_mut("head") {
+            let y_rot = -(head_y_rot - y_rot).to_radians();
+            let x_rot = x_rot.to_radians();
+            if self.looking {
+                head.y_rot = y_rot;
+                head.x_rot = x_rot;
+            } else {
+                head.y_rot += y_rot;
+                head.x_rot += x_rot;
+            }
+        }
+        if this.hurt_time > 0 || this.is_dead() {
+            glColor3f(1.0, 0.5, 0.5);
+        } else {
+            glColor3f(1.0, 1.0, 1.0);
+        }
+        self.model.render();
+        glPopMatrix();
+        glColor3f(1.0, 1.0, 1.0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn zombie() -> Zombie {
+        let level = Rc::new(RefCell::new(Level::with_dir(64, None)));
+        level.borrow_mut().update_columns(0.0, 0.0);
+        let ground = (level.borrow().depth * 2 / 3 + 1) as f32;
+        let mut zombie = Zombie::new(level, 0.0, 0.0, 0.0);
+        zombie.set_pos(0.5, ground + 0.9, 0.5);
+        zombie
+    }
+
+    #[test]
+    fn hits_the_player_once_every_few_ticks() {
+        let mut zombie = zombie();
+        let level = Rc::clone(&zombie.entity.level);
+        let mut hits = 0;
+        for tick in 1..=ATTACK_TICKS + 1 {
+            level.borrow_mut().player_bb = Some(zombie.entity.bb);
+            zombie.step();
+            hits += level.borrow_mut().take_player_hits().len();
+            let expected = if tick <= ATTACK_TICKS { 1 } else { 2 };
+            assert_eq!(hits, expected, "after {} ticks", tick);
+        }
+    }
+
+    #[test]
+    fn falls_over_and_is_gone_after_dying() {
+        let mut zombie = zombie();
+        assert!(zombie.is_attackable());
+        zombie.entity.health = 0;
+        assert!(!zombie.is_attackable());
+        for _ in 0..DEATH_TICKS - 1 {
+            zombie.step();
+        }
+        assert!(!zombie.is_removed());
+        zombie.step();
+        assert!(zombie.is_removed());
+    }
 }
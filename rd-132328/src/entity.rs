@@ -1,11 +1,44 @@
-use std::{cell::RefCell, rc::Rc};
+use std::{any::Any, cell::RefCell, rc::Rc};
 
 use lwrgl::LWRGL;
 
-use crate::{level::level::Level, phys::aabb::AABB};
+use crate::{
+    level::{
+        fluid::Fluid,
+        level::{Level, SPAWN_SIZE},
+        tesselator::Tesselator,
+    },
+    phys::{
+        aabb::AABB,
+        collision::{self, Collision},
+    },
+    saved_entity::{EntityData, SavedEntity},
+};
+
+/// How far a sneaking entity may lean over an edge is found in steps of this size.
+const SNEAK_EDGE_STEP: f32 = 0.05;
+/// Taken off the vertical speed of falling entities each tick.
+pub const GRAVITY: f32 = 0.005;
+/// Share of the vertical speed kept each tick in the air.
+pub const AIR_DRAG: f32 = 0.98;
+pub const JUMP_SPEED: f32 = 0.12;
+/// How hard overlapping entities push each other apart each tick.
+const PUSH_STRENGTH: f32 = 0.02;
+pub const MAX_HEALTH: i32 = 20;
+/// Ticks an entity flashes red after being hurt, it cannot be hurt again meanwhile.
+pub const HURT_TICKS: i32 = 30;
+/// Falls up to this many blocks deep do not hurt, every block further takes one health.
+const SAFE_FALL_DISTANCE: f32 = 3.0;
+const KNOCKBACK: f32 = 0.12;
+const KNOCKBACK_UP: f32 = 0.08;
+
+/// `angle` in degrees brought into -180 to 180, for turning the short way round.
+pub fn wrap_degrees(angle: f32) -> f32 {
+    (angle + 180.0).rem_euclid(360.0) - 180.0
+}
 
 pub struct Entity {
-    level: Rc<RefCell<Level>>,
+    pub level: Rc<RefCell<Level>>,
     pub xo: f32,
     pub yo: f32,
     pub zo: f32,
@@ -15,11 +48,22 @@ pub struct Entity {
     pub xd: f32,
     pub yd: f32,
     pub zd: f32,
-    pub y_rot: f32,
-    pub x_rot: f32,
+    pub y_rot_o: f32,
+    pub x_rot_o: f32,
+    pub y_rot: f32, // facing (sin, -cos) in x/z at 0 degrees, turning towards +x
+    pub x_rot: f32, // looking down when positive
     pub bb: AABB,
     pub on_ground: bool,
+    pub collision_normal: [f32; 3], // faces run into during the last move, see `Collision`
     pub height_offset: f32,
+    pub step_height: f32, // ledges up to this high are walked up without jumping
+    pub sneaking: bool,   // keeps the entity from walking off edges
+    pub noclip: bool,     // moves through tiles without colliding
+    pub in_fluid: Option<Fluid>,
+    pub health: i32,
+    pub hurt_time: i32,     // ticks left of the flash after being hurt
+    pub fall_distance: f32, // fallen since last standing on something
+    landed: bool,           // since last placed, dropping in from there is not a fall
 }
 
 impl Entity {
@@ -35,24 +79,195 @@ impl Entity {
             xd: 0.0,
             yd: 0.0,
             zd: 0.0,
+            y_rot_o: 0.0,
+            x_rot_o: 0.0,
             y_rot: 0.0,
             x_rot: 0.0,
             bb: AABB::new(0.0, 0.0, 0.0, 0.0, 0.0, 0.0),
             on_ground: false,
+            collision_normal: [0.0; 3],
             height_offset: 0.0,
+            step_height: 0.5,
+            sneaking: false,
+            noclip: false,
+            in_fluid: None,
+            health: MAX_HEALTH,
+            hurt_time: 0,
+            fall_distance: 0.0,
+            landed: false,
         };
 
         e.reset_pos();
 
         e
     }
+
+    fn collide(&self, xa: f32, ya: f32, za: f32) -> Collision {
+        let level = self.level.borrow();
+        let (xa, za) = if self.sneaking && self.on_ground {
+            self.stop_at_edge(&level, xa, za)
+        } else {
+            (xa, za)
+        };
+        let aabbs = level.get_cubes(
+            self.bb
+                .expand(xa, ya, za)
+                .expand(0.0, self.step_height, 0.0),
+        );
+        collision::resolve(
+            &aabbs,
+            &self.bb,
+            xa,
+            ya,
+            za,
+            self.step_height,
+            self.on_ground,
+        )
+    }
+
+    /// Takes `damage` off the entity's health, unless it was hurt just before. Returns whether
+    /// it took the damage.
+    pub fn hurt(&mut self, damage: i32) -> bool {
+        if self.hurt_time > 0 || self.is_dead() {
+            return false;
+        }
+        self.health -= damage;
+        self.hurt_time = HURT_TICKS;
+        true
+    }
+
+    /// Knocks the entity back and up, away from `x`/`z`.
+    pub fn knockback(&mut self, x: f32, z: f32) {
+        let xa = self.x - x;
+        let za = self.z - z;
+        let dist = (xa * xa + za * za).sqrt().max(0.01);
+        self.xd = self.xd / 2.0 + xa / dist * KNOCKBACK;
+        self.zd = self.zd / 2.0 + za / dist * KNOCKBACK;
+        self.yd = (self.yd / 2.0 + KNOCKBACK_UP).min(KNOCKBACK_UP);
+    }
+
+    pub fn data(&self) -> EntityData {
+        EntityData {
+            pos: [self.x, self.y, self.z],
+            motion: [self.xd, self.yd, self.zd],
+            rot: [self.y_rot, self.x_rot],
+            health: self.health,
+            hurt_time: self.hurt_time,
+            fall_distance: self.fall_distance,
+        }
+    }
+
+    /// Picks up where the entity saved as `data` left off, keeping its own bounding box size.
+    pub fn load(&mut self, data: &EntityData) {
+        let [x, y, z] = data.pos;
+        let bb = &mut self.bb;
+        bb.move_(
+            x - (bb.x0 + bb.x1) / 2.0,
+            y - self.height_offset - bb.y0,
+            z - (bb.z0 + bb.z1) / 2.0,
+        );
+        (self.x, self.y, self.z) = (x, y, z);
+        (self.xo, self.yo, self.zo) = (x, y, z);
+        [self.xd, self.yd, self.zd] = data.motion;
+        [self.y_rot, self.x_rot] = data.rot;
+        (self.y_rot_o, self.x_rot_o) = (self.y_rot, self.x_rot);
+        self.health = data.health;
+        self.hurt_time = data.hurt_time;
+        self.fall_distance = data.fall_distance;
+        self.landed = true;
+    }
+
+    pub fn is_dead(&self) -> bool {
+        self.health <= 0
+    }
+
+    /// Hurts the entity for the distance it fell once it lands. The drop after being spawned or
+    /// teleported somewhere up in the air does not count.
+    fn fall(&mut self, collision: &Collision) {
+        if self.noclip || self.in_fluid.is_some() {
+            self.fall_distance = 0.0;
+        } else if collision.on_ground() {
+            let damage = (self.fall_distance - SAFE_FALL_DISTANCE).ceil() as i32;
+            if damage > 0 {
+                self.hurt(damage);
+            }
+            self.fall_distance = 0.0;
+            self.landed = true;
+        } else if collision.ya < 0.0 && self.landed {
+            self.fall_distance -= collision.ya;
+        }
+    }
+
+    /// Pushes this entity and `other` away from each other horizontally, the harder the closer
+    /// they are.
+    pub fn push(&mut self, other: &mut Entity) {
+        let mut xa = other.x - self.x;
+        let mut za = other.z - self.z;
+        let dist = xa.abs().max(za.abs());
+        if dist < 0.01 {
+            return;
+        }
+        let dist = dist.sqrt();
+        let f = (1.0 / dist).min(1.0) * PUSH_STRENGTH / dist;
+        xa *= f;
+        za *= f;
+        self.xd -= xa;
+        self.zd -= za;
+        other.xd += xa;
+        other.zd += za;
+    }
+
+    /// Shortens the motion so that the entity keeps standing on something.
+    fn stop_at_edge(&self, level: &Level, xa: f32, za: f32) -> (f32, f32) {
+        let on_ground_after = |xa: f32, za: f32| {
+            let mut bb = self.bb;
+            bb.move_(xa, -1.0, za);
+            !level.get_cubes(bb).is_empty()
+        };
+        let shorten = |a: f32| {
+            if a.abs() < SNEAK_EDGE_STEP {
+                0.0
+            } else {
+                a - SNEAK_EDGE_STEP * a.signum()
+            }
+        };
+        let mut xa = xa;
+        let mut za = za;
+        while xa != 0.0 && !on_ground_after(xa, 0.0) {
+            xa = shorten(xa);
+        }
+        while za != 0.0 && !on_ground_after(0.0, za) {
+            za = shorten(za);
+        }
+        while xa != 0.0 && za != 0.0 && !on_ground_after(xa, za) {
+            xa = shorten(xa);
+            za = shorten(za);
+        }
+        (xa, za)
+    }
 }
 
 impl EntityTrait for Entity {
+    fn entity(&self) -> &Entity {
+        self
+    }
+
+    fn entity_mut(&mut self) -> &mut Entity {
+        self
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
     fn reset_pos(&mut self) {
-        let x = rand::random::<f32>() * self.level.borrow().width as f32;
+        let x = rand::random::<f32>() * SPAWN_SIZE as f32;
         let y = (self.level.borrow().depth + 10) as f32;
-        let z = rand::random::<f32>() * self.level.borrow().height as f32;
+        let z = rand::random::<f32>() * SPAWN_SIZE as f32;
         self.set_pos(x, y, z);
     }
 
@@ -63,9 +278,13 @@ impl EntityTrait for Entity {
         let w = 0.3;
         let h = 0.9;
         self.bb = AABB::new(x - w, y - h, z - w, x + w, y + h, z + w);
+        self.fall_distance = 0.0;
+        self.landed = false;
     }
 
     fn turn(&mut self, xo: f32, yo: f32) {
+        let y_rot = self.y_rot;
+        let x_rot = self.x_rot;
         self.y_rot = (self.y_rot as f64 + (xo as f64 * 0.15)) as f32;
         self.x_rot = (self.x_rot as f64 + (yo as f64 * 0.15)) as f32;
 
@@ -75,6 +294,9 @@ impl EntityTrait for Entity {
         if self.x_rot > 90.0 {
             self.x_rot = 90.0;
         }
+        // turning between ticks is not interpolated, it shows right away
+        self.y_rot_o += self.y_rot - y_rot;
+        self.x_rot_o += self.x_rot - x_rot;
     }
 
     fn tick(&mut self, _lwrgl: &LWRGL) {
@@ -84,33 +306,33 @@ impl EntityTrait for Entity {
     }
 
     fn move_(&mut self, xa: f32, ya: f32, za: f32) {
-        let mut xa = xa;
-        let mut ya = ya;
-        let mut za = za;
-        let xa_org = xa;
-        let ya_org = ya;
-        let za_org = za;
-        let aabbs = self.level.borrow().get_cubes(self.bb.expand(xa, ya, za));
-        for aabb in &aabbs {
-            ya = aabb.clip_y_collide(&self.bb, ya);
-        }
-        self.bb.move_(0.0, ya, 0.0);
-        for aabb in &aabbs {
-            xa = aabb.clip_x_collide(&self.bb, xa);
-        }
-        self.bb.move_(xa, 0.0, 0.0);
-        for aabb in &aabbs {
-            za = aabb.clip_z_collide(&self.bb, za);
-        }
-        self.bb.move_(0.0, 0.0, za);
-        self.on_ground = ya_org != ya && ya_org < 0.0;
-        if xa_org != xa {
+        let collision = if self.noclip {
+            Collision {
+                xa,
+                ya,
+                za,
+                ..Default::default()
+            }
+        } else {
+            self.collide(xa, ya, za)
+        };
+        self.in_fluid = self.level.borrow().fluid_in(&self.bb);
+        if let Some(fluid) = self.in_fluid {
+            self.xd *= fluid.drag();
+            self.yd = self.yd * fluid.drag() + fluid.buoyancy();
+            self.zd *= fluid.drag();
+        }
+        self.bb.move_(collision.xa, collision.ya, collision.za);
+        self.collision_normal = collision.normal;
+        self.on_ground = collision.on_ground();
+        self.fall(&collision);
+        if collision.normal[0] != 0.0 {
             self.xd = 0.0;
         }
-        if ya_org != ya {
+        if collision.normal[1] != 0.0 {
             self.yd = 0.0;
         }
-        if za_org != za {
+        if collision.normal[2] != 0.0 {
             self.zd = 0.0;
         }
         self.x = (self.bb.x0 + self.bb.x1) / 2.0;
@@ -136,10 +358,172 @@ impl EntityTrait for Entity {
 }
 
 pub trait EntityTrait {
+    fn entity(&self) -> &Entity;
+    fn entity_mut(&mut self) -> &mut Entity;
+    /// For getting at the concrete type of an entity in the level.
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
     fn reset_pos(&mut self);
     fn set_pos(&mut self, x: f32, y: f32, z: f32);
     fn turn(&mut self, xo: f32, yo: f32);
     fn tick(&mut self, lwrgl: &LWRGL);
     fn move_(&mut self, xa: f32, ya: f32, za: f32);
     fn move_relative(&mut self, xa: f32, za: f32, speed: f32);
+
+    /// Whether the entity is done and should be taken out of the level.
+    fn is_removed(&self) -> bool {
+        false
+    }
+
+    /// Whether other entities push this one away when they overlap.
+    fn is_pushable(&self) -> bool {
+        false
+    }
+
+    /// Whether the player can hit the entity.
+    fn is_attackable(&self) -> bool {
+        false
+    }
+
+    /// What to save of the entity, none for entities that are not kept.
+    fn save(&self) -> Option<SavedEntity> {
+        None
+    }
+
+    /// The texture `render` draws with. It is bound before rendering, once for all the entities
+    /// sharing it.
+    fn texture(&self) -> Option<&str> {
+        None
+    }
+
+    unsafe fn render(&mut self, _t: &mut Tesselator, _a: f32) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{character::zombie::Zombie, player::Player};
+
+    fn drop_from(height: f32) -> Entity {
+        let level = Rc::new(RefCell::new(Level::with_dir(64, None)));
+        level.borrow_mut().update_columns(0.0, 0.0);
+        let ground = (level.borrow().depth * 2 / 3 + 1) as f32;
+        let mut entity = Entity::new(level);
+        entity.set_pos(0.5, ground + height + 0.9, 0.5);
+        // as if it walked off a ledge rather than being put up there
+        entity.landed = true;
+        land(&mut entity);
+        entity
+    }
+
+    fn land(entity: &mut Entity) {
+        while !entity.on_ground {
+            entity.yd -= GRAVITY;
+            entity.move_(entity.xd, entity.yd, entity.zd);
+            entity.yd *= AIR_DRAG;
+        }
+    }
+
+    #[test]
+    fn turns_right_away_and_the_short_way_round() {
+        let mut entity = Entity::new(Rc::new(RefCell::new(Level::with_dir(64, None))));
+        entity.y_rot = 10.0;
+        entity.y_rot_o = 5.0;
+        entity.turn(100.0, 1000.0);
+        assert_eq!(entity.y_rot - entity.y_rot_o, 5.0);
+        assert_eq!((entity.x_rot, entity.x_rot_o), (90.0, 90.0));
+        assert_eq!(wrap_degrees(350.0), -10.0);
+        assert_eq!(wrap_degrees(-190.0), 170.0);
+    }
+
+    #[test]
+    fn falls_hurt_only_past_a_few_blocks() {
+        assert_eq!(drop_from(3.0).health, MAX_HEALTH);
+        assert_eq!(drop_from(10.0).health, MAX_HEALTH - 7);
+    }
+
+    #[test]
+    fn hurts_only_once_in_a_while() {
+        let mut entity = Entity::new(Rc::new(RefCell::new(Level::with_dir(64, None))));
+        assert!(entity.hurt(5));
+        assert!(!entity.hurt(5));
+        assert_eq!(entity.health, MAX_HEALTH - 5);
+        entity.hurt_time = 0;
+        assert!(entity.hurt(MAX_HEALTH));
+        entity.hurt_time = 0;
+        // nothing left to take
+        assert!(!entity.hurt(5));
+        assert!(entity.is_dead());
+    }
+
+    #[test]
+    fn knocks_back_away_from_the_hit() {
+        let mut entity = Entity::new(Rc::new(RefCell::new(Level::with_dir(64, None))));
+        entity.set_pos(10.0, 50.0, 10.0);
+        entity.knockback(9.0, 10.0);
+        assert!(entity.xd > 0.0);
+        assert_eq!(entity.zd, 0.0);
+        assert!(entity.yd > 0.0);
+        entity.knockback(10.0, 12.0);
+        assert!(entity.zd < 0.0);
+    }
+
+    /// Walks an entity towards +x for a while, off the ground onto a pit from x = 3 on.
+    fn walk_to_ledge(sneaking: bool) -> Entity {
+        let level = Rc::new(RefCell::new(Level::with_dir(64, None)));
+        level.borrow_mut().update_columns(0.0, 0.0);
+        let ground = level.borrow().depth * 2 / 3 + 1;
+        for x in 3..8 {
+            for z in -2..3 {
+                for y in ground - 4..ground {
+                    level.borrow_mut().set_tile(x, y, z, 0);
+                }
+            }
+        }
+        let mut entity = Entity::new(level);
+        entity.set_pos(1.5, ground as f32 + 0.9, 0.5);
+        land(&mut entity);
+        entity.sneaking = sneaking;
+        for _ in 0..100 {
+            entity.xd += 0.02;
+            entity.yd -= GRAVITY;
+            entity.move_(entity.xd, entity.yd, entity.zd);
+            entity.xd *= 0.5;
+            entity.yd *= AIR_DRAG;
+        }
+        entity
+    }
+
+    #[test]
+    fn sneaking_stops_at_the_edge() {
+        let ground = (64 * 2 / 3 + 1) as f32;
+        let entity = walk_to_ledge(true);
+        assert!(entity.on_ground);
+        assert_eq!(entity.bb.y0, ground);
+        // leaning over the edge, but still standing on it
+        assert!(entity.bb.x0 < 3.0 && entity.bb.x1 > 3.0);
+
+        let entity = walk_to_ledge(false);
+        assert!(entity.bb.y0 < ground);
+        assert!(entity.bb.x0 > 3.0);
+    }
+
+    #[test]
+    fn spawning_up_in_the_air_does_not_hurt() {
+        let level = Rc::new(RefCell::new(Level::with_dir(64, None)));
+        let mut player = Player::new(Rc::clone(&level));
+        let mut zombie = Zombie::new(Rc::clone(&level), 0.0, 0.0, 0.0);
+        for entity in [&mut player.entity, &mut zombie.entity] {
+            let bb = entity.bb;
+            level
+                .borrow_mut()
+                .update_columns((bb.x0 + bb.x1) / 2.0, (bb.z0 + bb.z1) / 2.0);
+            land(entity);
+            assert_eq!(entity.health, MAX_HEALTH);
+        }
+        player.entity.set_pos(0.5, 100.0, 0.5);
+        level.borrow_mut().update_columns(0.5, 0.5);
+        land(&mut player.entity);
+        assert_eq!(player.entity.health, MAX_HEALTH);
+    }
 }
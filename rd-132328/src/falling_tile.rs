@@ -0,0 +1,181 @@
+use std::{any::Any, cell::RefCell, rc::Rc};
+
+use lwrgl::glu_sys::{glPopMatrix, glPushMatrix, glTranslatef};
+use lwrgl::LWRGL;
+
+use crate::{
+    entity::{Entity, EntityTrait, AIR_DRAG, GRAVITY},
+    level::{level::Level, tesselator::Tesselator, tile},
+    phys::aabb::AABB,
+    saved_entity::SavedEntity,
+};
+
+/// A tile that lost its support, falling until it lands and is put back into the level.
+pub struct FallingTile {
+    pub entity: Entity,
+    pub tile: i32,
+    removed: bool, // landed or fell out of the level
+}
+
+impl FallingTile {
+    /// Starts the tile with `id` falling from where it was at `x`/`y`/`z`.
+    pub fn new(level: Rc<RefCell<Level>>, x: i32, y: i32, z: i32, id: i32) -> FallingTile {
+        let mut entity = Entity::new(level);
+        entity.step_height = 0.0;
+        let mut falling_tile = FallingTile {
+            entity,
+            tile: id,
+            removed: false,
+        };
+        falling_tile.set_pos(x as f32 + 0.5, y as f32, z as f32 + 0.5);
+        let this = &mut falling_tile.entity;
+        this.xo = this.x;
+        this.yo = this.y;
+        this.zo = this.z;
+        falling_tile
+    }
+
+    fn fall(&mut self) {
+        let this = &mut self.entity;
+        this.xo = this.x;
+        this.yo = this.y;
+        this.zo = this.z;
+        this.yd -= GRAVITY;
+        this.move_(0.0, this.yd, 0.0);
+        this.yd *= AIR_DRAG;
+        if this.on_ground {
+            self.land();
+        } else if this.y < 0.0 {
+            self.removed = true;
+        }
+    }
+
+    /// Puts the tile back into the level where it landed, unless something took its place.
+    fn land(&mut self) {
+        let x = self.entity.x.floor() as i32;
+        let y = (self.entity.y + 0.5).floor() as i32;
+        let z = self.entity.z.floor() as i32;
+        let mut level = self.entity.level.borrow_mut();
+        if !level.is_solid_tile(x, y, z) {
+            level.set_tile(x, y, z, self.tile);
+        }
+        self.removed = true;
+    }
+}
+
+impl EntityTrait for FallingTile {
+    fn entity(&self) -> &Entity {
+        &self.entity
+    }
+
+    fn entity_mut(&mut self) -> &mut Entity {
+        &mut self.entity
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn reset_pos(&mut self) {
+        self.entity.reset_pos();
+    }
+
+    /// Places the tile with its bottom centered on `x`/`y`/`z`.
+    fn set_pos(&mut self, x: f32, y: f32, z: f32) {
+        let this = &mut self.entity;
+        this.x = x;
+        this.y = y;
+        this.z = z;
+        let w = 0.49;
+        this.bb = AABB::new(x - w, y, z - w, x + w, y + 0.98, z + w);
+    }
+
+    fn turn(&mut self, xo: f32, yo: f32) {
+        self.entity.turn(xo, yo);
+    }
+
+    fn tick(&mut self, _lwrgl: &LWRGL) {
+        self.fall();
+    }
+
+    fn move_(&mut self, xa: f32, ya: f32, za: f32) {
+        self.entity.move_(xa, ya, za);
+    }
+
+    fn move_relative(&mut self, xa: f32, za: f32, speed: f32) {
+        self.entity.move_relative(xa, za, speed);
+    }
+
+    fn is_removed(&self) -> bool {
+        self.removed
+    }
+
+    fn save(&self) -> Option<SavedEntity> {
+        Some(SavedEntity::FallingTile {
+            entity: self.entity.data(),
+            tile: self.tile,
+        })
+    }
+
+    fn texture(&self) -> Option<&str> {
+        Some("terrain.png")
+    }
+
+    unsafe fn render(&mut self, t: &mut Tesselator, a: f32) {
+        let tile = match tile::by_id(self.tile) {
+            Some(tile) => tile,
+            None => return,
+        };
+        let this = &self.entity;
+        let x = this.xo + (this.x - this.xo) * a;
+        let y = this.yo + (this.y - this.yo) * a;
+        let z = this.zo + (this.z - this.zo) * a;
+        let br = self.entity.level.borrow().get_brightness(
+            x.floor() as i32,
+            (y + 0.5).floor() as i32,
+            z.floor() as i32,
+        );
+        glPushMatrix();
+        glTranslatef(x - 0.5, y, z - 0.5);
+        t.init();
+        tile.render_shaded(t, br);
+        t.flush();
+        glPopMatrix();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sand_falls_and_lands_on_the_ground() {
+        let level = Rc::new(RefCell::new(Level::with_dir(64, None)));
+        level.borrow_mut().update_columns(0.0, 0.0);
+        let ground = level.borrow().depth * 2 / 3 + 1;
+        level.borrow_mut().set_tile(3, ground + 5, 3, tile::SAND.id);
+
+        let mut falling = vec![];
+        for _ in 0..10 {
+            level.borrow_mut().tick();
+            falling.extend(level.borrow_mut().take_falling());
+        }
+        assert_eq!(falling, [(3, ground + 5, 3, tile::SAND.id)]);
+        assert_eq!(level.borrow().get_tile(3, ground + 5, 3), 0);
+
+        let mut falling_tile = FallingTile::new(Rc::clone(&level), 3, ground + 5, 3, tile::SAND.id);
+        for _ in 0..200 {
+            if falling_tile.removed {
+                break;
+            }
+            falling_tile.fall();
+        }
+        assert!(falling_tile.removed);
+        assert_eq!(level.borrow().get_tile(3, ground, 3), tile::SAND.id);
+        assert_eq!(level.borrow().get_tile(3, ground + 1, 3), 0);
+    }
+}
@@ -0,0 +1,207 @@
+//! Renders fixed scenes through `LevelRenderer` and compares them against the reference images
+//! in `tests/golden/`.
+//!
+//! The rendering tests need an OpenGL context, so they are ignored by default. With a software
+//! renderer they can be run headless, e.g.
+//! `xvfb-run -a env LIBGL_ALWAYS_SOFTWARE=1 cargo test -- --ignored`.
+//! Set `UPDATE_GOLDEN=1` to write the current output as the new reference images instead, see
+//! `tests/golden/README.md`. A missing reference fails the test.
+
+use std::cell::RefCell;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::sync::Mutex;
+
+use image::{Rgba, RgbaImage};
+
+use crate::camera::Camera;
+use crate::level::level::Level;
+use crate::level::tile;
+use crate::screenshot::render_frame;
+
+const WIDTH: i32 = 320;
+const HEIGHT: i32 = 240;
+/// Channel difference up to which two pixels are considered the same.
+const PIXEL_TOLERANCE: u8 = 8;
+/// Share of pixels that may differ before an image no longer matches its reference.
+const MAX_DIFF_RATIO: f64 = 0.002;
+
+// only one GL context at a time
+static GL: Mutex<()> = Mutex::new(());
+
+/// Compares two images, failing if they differ in size or in too many pixels.
+fn compare(actual: &RgbaImage, expected: &RgbaImage) -> Result<(), String> {
+    if actual.dimensions() != expected.dimensions() {
+        return Err(format!(
+            "size {:?} differs from reference size {:?}",
+            actual.dimensions(),
+            expected.dimensions()
+        ));
+    }
+    let differing = actual
+        .pixels()
+        .zip(expected.pixels())
+        .filter(|(a, e)| (0..3).any(|c| a[c].abs_diff(e[c]) > PIXEL_TOLERANCE))
+        .count();
+    let total = (actual.width() * actual.height()) as f64;
+    if differing as f64 / total > MAX_DIFF_RATIO {
+        return Err(format!("{} of {} pixels differ", differing, total));
+    }
+    Ok(())
+}
+
+/// Marks every pixel that differs from the reference in red.
+fn diff_image(actual: &RgbaImage, expected: &RgbaImage) -> RgbaImage {
+    RgbaImage::from_fn(actual.width(), actual.height(), |x, y| {
+        let a = actual.get_pixel(x, y);
+        let e = expected.get_pixel(x, y);
+        if (0..3).any(|c| a[c].abs_diff(e[c]) > PIXEL_TOLERANCE) {
+            Rgba([255, 0, 0, 255])
+        } else {
+            Rgba([a[0] / 3, a[1] / 3, a[2] / 3, 255])
+        }
+    })
+}
+
+fn assert_golden(name: &str, actual: &RgbaImage) {
+    let path = PathBuf::from("tests/golden").join(format!("{}.png", name));
+    if env::var("UPDATE_GOLDEN").is_ok() {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        actual.save(&path).unwrap();
+        return;
+    }
+    let expected = match image::open(&path) {
+        Ok(img) => img.to_rgba8(),
+        Err(e) => panic!(
+            "no reference image {} ({}), create it with UPDATE_GOLDEN=1 as described in \
+             tests/golden/README.md and commit it",
+            path.display(),
+            e
+        ),
+    };
+    if let Err(e) = compare(actual, &expected) {
+        let out = PathBuf::from("target/golden");
+        fs::create_dir_all(&out).unwrap();
+        actual.save(out.join(format!("{}.png", name))).unwrap();
+        if actual.dimensions() == expected.dimensions() {
+            diff_image(actual, &expected)
+                .save(out.join(format!("{}.diff.png", name)))
+                .unwrap();
+        }
+        panic!("{} does not match its reference: {}", name, e);
+    }
+}
+
+/// The flat generated terrain with a few structures that show every kind of face.
+fn fixed_level() -> Rc<RefCell<Level>> {
+    let level = Rc::new(RefCell::new(Level::with_dir(64, None)));
+    {
+        let mut level = level.borrow_mut();
+        level.update_columns(8.0, 8.0);
+        let ground = level.depth * 2 / 3 + 1;
+        // one pillar of each tile, so that their textures can be told apart
+        let pillars = [
+            &tile::ROCK,
+            &tile::GRASS,
+            &tile::DIRT,
+            &tile::COBBLESTONE,
+            &tile::PLANKS,
+        ];
+        for (i, tile) in pillars.iter().enumerate() {
+            for y in ground..ground + 3 {
+                level.set_tile(4 + i as i32 * 2, y, 4, tile.id);
+            }
+        }
+        // a roof casting a shadow on the ground and the pillar below it
+        for x in 4..9 {
+            for z in 8..13 {
+                level.set_tile(x, ground + 3, z, tile::PLANKS.id);
+            }
+        }
+        level.set_tile(6, ground, 10, tile::COBBLESTONE.id);
+        // a pit, to look at faces from below the surface
+        for x in 12..15 {
+            for z in 8..11 {
+                level.set_tile(x, ground - 1, z, 0);
+                level.set_tile(x, ground - 2, z, 0);
+            }
+        }
+    }
+    level
+}
+
+/// A camera at `x`/`y`/`z` looking along -z at `y_rot` 0 and down at positive `x_rot`.
+fn camera_at(x: f32, y: f32, z: f32, y_rot: f32, x_rot: f32) -> Camera {
+    let mut camera = Camera::new(70.0, WIDTH as f32 / HEIGHT as f32);
+    camera.x = x;
+    camera.y = y;
+    camera.z = z;
+    camera.y_rot = y_rot;
+    camera.x_rot = x_rot;
+    camera.distance = 0.0;
+    camera
+}
+
+fn render_golden(name: &str, camera: Camera) {
+    let _gl = GL.lock().unwrap_or_else(|e| e.into_inner());
+    let img = render_frame(fixed_level(), &camera, WIDTH, HEIGHT);
+    assert_golden(name, &img);
+}
+
+#[test]
+#[ignore = "needs an OpenGL context"]
+fn pillars_from_the_front() {
+    render_golden("pillars", camera_at(8.5, 45.5, -2.0, 180.0, 15.0));
+}
+
+#[test]
+#[ignore = "needs an OpenGL context"]
+fn shadow_under_roof() {
+    render_golden("shadow", camera_at(2.0, 45.5, 14.0, 45.0, 20.0));
+}
+
+#[test]
+#[ignore = "needs an OpenGL context"]
+fn pit_from_above() {
+    render_golden("pit", camera_at(13.5, 50.0, 9.5, 0.0, 90.0));
+}
+
+#[test]
+#[ignore = "needs an OpenGL context"]
+fn pillars_from_behind() {
+    render_golden("pillars_behind", camera_at(8.5, 45.5, 11.0, 0.0, 15.0));
+}
+
+#[test]
+fn identical_images_match() {
+    let img = RgbaImage::from_pixel(4, 4, Rgba([10, 20, 30, 255]));
+    assert!(compare(&img, &img.clone()).is_ok());
+}
+
+#[test]
+fn differences_within_tolerance_match() {
+    let expected = RgbaImage::from_pixel(4, 4, Rgba([10, 20, 30, 255]));
+    let actual = RgbaImage::from_pixel(4, 4, Rgba([10 + PIXEL_TOLERANCE, 20, 30, 255]));
+    assert!(compare(&actual, &expected).is_ok());
+}
+
+#[test]
+fn differing_pixels_do_not_match() {
+    let expected = RgbaImage::from_pixel(4, 4, Rgba([10, 20, 30, 255]));
+    let mut actual = expected.clone();
+    actual.put_pixel(1, 1, Rgba([200, 20, 30, 255]));
+    assert!(compare(&actual, &expected).is_err());
+    assert_eq!(
+        diff_image(&actual, &expected).get_pixel(1, 1),
+        &Rgba([255, 0, 0, 255])
+    );
+}
+
+#[test]
+fn differing_sizes_do_not_match() {
+    let expected = RgbaImage::new(4, 4);
+    let actual = RgbaImage::new(4, 5);
+    assert!(compare(&actual, &expected).is_err());
+}
@@ -0,0 +1,83 @@
+use image::io::Reader as ImageReader;
+
+use crate::glu::*;
+use crate::level::tesselator::Tesselator;
+use crate::textures::load_texture;
+
+/// A bitmap font read from a 128x128 atlas of 16x16 glyphs, 8x8 pixels each.
+pub struct Font {
+    char_widths: [i32; 256],
+    texture: i32,
+}
+
+impl Font {
+    pub fn new(name: &str) -> Font {
+        let img = ImageReader::open(name)
+            .unwrap()
+            .decode()
+            .unwrap()
+            .to_rgba8();
+        let mut char_widths = [0; 256];
+        for (i, char_width) in char_widths.iter_mut().enumerate() {
+            let xt = (i % 16) as u32 * 8;
+            let yt = (i / 16) as u32 * 8;
+            // the glyph is as wide as its rightmost non-empty column, plus one pixel of spacing
+            let mut x = 7;
+            while x >= 0 {
+                let empty = (0..8).all(|y| img.get_pixel(xt + x as u32, yt + y)[3] == 0);
+                if !empty {
+                    break;
+                }
+                x -= 1;
+            }
+            *char_width = if i == ' ' as usize { 4 } else { x + 2 };
+        }
+
+        Font {
+            char_widths,
+            texture: load_texture(name, GL_NEAREST as i32),
+        }
+    }
+
+    pub fn draw_shadow(&self, t: &mut Tesselator, text: &str, x: i32, y: i32, color: i32) {
+        self.draw(t, text, x + 1, y + 1, (color & 0xFCFCFC) >> 2);
+        self.draw(t, text, x, y, color);
+    }
+
+    pub fn draw(&self, t: &mut Tesselator, text: &str, x: i32, y: i32, color: i32) {
+        unsafe {
+            glEnable(GL_TEXTURE_2D);
+            glBindTexture(GL_TEXTURE_2D, self.texture as GLuint);
+        }
+        t.init();
+        t.color(
+            (color >> 16 & 0xFF) as f32 / 255.0,
+            (color >> 8 & 0xFF) as f32 / 255.0,
+            (color & 0xFF) as f32 / 255.0,
+        );
+        let mut xo = 0;
+        for c in text.bytes() {
+            let u0 = (c % 16) as f32 * 8.0 / 128.0;
+            let v0 = (c / 16) as f32 * 8.0 / 128.0;
+            let u1 = u0 + 8.0 / 128.0;
+            let v1 = v0 + 8.0 / 128.0;
+            let x0 = (x + xo) as f32;
+            let y0 = y as f32;
+            let x1 = x0 + 8.0;
+            let y1 = y0 + 8.0;
+            t.tex(u0, v1);
+            t.vertex(x0, y1, 0.0);
+            t.tex(u1, v1);
+            t.vertex(x1, y1, 0.0);
+            t.tex(u1, v0);
+            t.vertex(x1, y0, 0.0);
+            t.tex(u0, v0);
+            t.vertex(x0, y0, 0.0);
+            xo += self.char_widths[c as usize];
+        }
+        t.flush();
+        unsafe {
+            glDisable(GL_TEXTURE_2D);
+        }
+    }
+}
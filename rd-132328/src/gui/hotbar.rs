@@ -0,0 +1,100 @@
+use crate::glu::*;
+use crate::level::tesselator::Tesselator;
+use crate::level::tile::{self, Tile};
+use crate::textures::load_texture;
+
+pub const SLOT_SIZE: i32 = 20;
+
+/// The strip of tiles at the bottom of the screen that the player picks from when building.
+pub struct Hotbar {
+    slots: Vec<&'static Tile>,
+    selected: usize,
+}
+
+impl Hotbar {
+    pub fn new() -> Hotbar {
+        Hotbar {
+            slots: vec![
+                &tile::ROCK,
+                &tile::GRASS,
+                &tile::DIRT,
+                &tile::COBBLESTONE,
+                &tile::PLANKS,
+                &tile::WATER,
+                &tile::LAVA,
+                &tile::SAPLING,
+                &tile::SAND,
+                &tile::GRAVEL,
+            ],
+            selected: 0,
+        }
+    }
+
+    pub fn selected_tile(&self) -> i32 {
+        self.slots[self.selected].id
+    }
+
+    pub fn select(&mut self, slot: usize) {
+        if slot < self.slots.len() {
+            self.selected = slot;
+        }
+    }
+
+    /// Moves the selection by `d` slots, wrapping around at both ends.
+    pub fn scroll(&mut self, d: i32) {
+        let len = self.slots.len() as i32;
+        self.selected = (self.selected as i32 + d).rem_euclid(len) as usize;
+    }
+
+    pub fn render(&self, t: &mut Tesselator, screen_width: i32, screen_height: i32) {
+        let len = self.slots.len() as i32;
+        let x0 = screen_width / 2 - len * SLOT_SIZE / 2;
+        let y0 = screen_height - SLOT_SIZE - 2;
+
+        unsafe {
+            glColor4f(0.0, 0.0, 0.0, 0.5);
+        }
+        t.init();
+        fill(t, x0, y0, x0 + len * SLOT_SIZE, y0 + SLOT_SIZE);
+        t.flush();
+
+        let xs = x0 + self.selected as i32 * SLOT_SIZE;
+        unsafe {
+            glColor4f(1.0, 1.0, 1.0, 1.0);
+        }
+        t.init();
+        fill(t, xs, y0, xs + SLOT_SIZE, y0 + 1);
+        fill(t, xs, y0 + SLOT_SIZE - 1, xs + SLOT_SIZE, y0 + SLOT_SIZE);
+        fill(t, xs, y0, xs + 1, y0 + SLOT_SIZE);
+        fill(t, xs + SLOT_SIZE - 1, y0, xs + SLOT_SIZE, y0 + SLOT_SIZE);
+        t.flush();
+
+        let id = load_texture("terrain.png", GL_NEAREST as i32);
+        unsafe {
+            glEnable(GL_TEXTURE_2D);
+            glBindTexture(GL_TEXTURE_2D, id as GLuint);
+            for (i, tile) in self.slots.iter().enumerate() {
+                let x = x0 + i as i32 * SLOT_SIZE + SLOT_SIZE / 2;
+                let y = y0 + SLOT_SIZE / 2;
+                glPushMatrix();
+                glTranslatef(x as f32, y as f32, 10.0);
+                glScalef(9.0, -9.0, 9.0);
+                glRotatef(30.0, 1.0, 0.0, 0.0);
+                glRotatef(45.0, 0.0, 1.0, 0.0);
+                glTranslatef(-0.5, -0.5, -0.5);
+                t.init();
+                tile.render_gui(t);
+                t.flush();
+                glPopMatrix();
+            }
+            glDisable(GL_TEXTURE_2D);
+        }
+    }
+}
+
+pub fn fill(t: &mut Tesselator, x0: i32, y0: i32, x1: i32, y1: i32) {
+    t.vertex(x0 as f32, y1 as f32, 0.0);
+    t.vertex(x1 as f32, y1 as f32, 0.0);
+    t.vertex(x1 as f32, y0 as f32, 0.0);
+    t.vertex(x0 as f32, y0 as f32, 0.0);
+}
@@ -0,0 +1,102 @@
+use crate::entity::MAX_HEALTH;
+use crate::glu::*;
+use crate::level::tesselator::Tesselator;
+
+use super::font::Font;
+use super::hotbar::{fill, Hotbar, SLOT_SIZE};
+
+/// Size of one overlay pixel in screen pixels.
+const GUI_SCALE: i32 = 2;
+/// Width of one point of health in overlay pixels.
+const HEALTH_SCALE: i32 = 4;
+const HEALTH_BAR_HEIGHT: i32 = 4;
+
+/// Draws the 2D overlay on top of the rendered world.
+pub struct Hud {
+    font: Font,
+    pub hotbar: Hotbar,
+    pub show_debug: bool,
+    t: Tesselator,
+}
+
+impl Hud {
+    pub fn new() -> Hud {
+        Hud {
+            font: Font::new("default.png"),
+            hotbar: Hotbar::new(),
+            show_debug: false,
+            t: Tesselator::new(),
+        }
+    }
+
+    /// Renders the crosshair, the player's `health` and, if enabled, the given lines of the debug
+    /// screen.
+    pub fn render(&mut self, width: i32, height: i32, health: i32, debug_lines: &[String]) {
+        let screen_width = width / GUI_SCALE;
+        let screen_height = height / GUI_SCALE;
+        unsafe {
+            glClear(GL_DEPTH_BUFFER_BIT);
+            glMatrixMode(GL_PROJECTION);
+            glLoadIdentity();
+            glOrtho(
+                0.0,
+                screen_width as f64,
+                screen_height as f64,
+                0.0,
+                100.0,
+                300.0,
+            );
+            glMatrixMode(GL_MODELVIEW);
+            glLoadIdentity();
+            glTranslatef(0.0, 0.0, -200.0);
+            glDisable(GL_CULL_FACE);
+            glDisable(GL_FOG);
+            glEnable(GL_BLEND);
+            glBlendFunc(GL_SRC_ALPHA, GL_ONE_MINUS_SRC_ALPHA);
+        }
+
+        self.render_crosshair(screen_width / 2, screen_height / 2);
+        self.hotbar.render(&mut self.t, screen_width, screen_height);
+        self.render_health(health, screen_width, screen_height);
+        if self.show_debug {
+            for (i, line) in debug_lines.iter().enumerate() {
+                self.font
+                    .draw_shadow(&mut self.t, line, 2, 2 + i as i32 * 10, 0xFFFFFF);
+            }
+        }
+
+        unsafe {
+            glDisable(GL_BLEND);
+        }
+    }
+
+    /// A bar above the hotbar, red for the health left.
+    fn render_health(&mut self, health: i32, screen_width: i32, screen_height: i32) {
+        let x0 = screen_width / 2 - MAX_HEALTH * HEALTH_SCALE / 2;
+        let y0 = screen_height - SLOT_SIZE - 2 - HEALTH_BAR_HEIGHT - 2;
+        let x1 = x0 + health.clamp(0, MAX_HEALTH) * HEALTH_SCALE;
+        let x2 = x0 + MAX_HEALTH * HEALTH_SCALE;
+        unsafe {
+            glColor4f(0.0, 0.0, 0.0, 0.5);
+        }
+        self.t.init();
+        fill(&mut self.t, x1, y0, x2, y0 + HEALTH_BAR_HEIGHT);
+        self.t.flush();
+        unsafe {
+            glColor4f(0.8, 0.1, 0.1, 1.0);
+        }
+        self.t.init();
+        fill(&mut self.t, x0, y0, x1, y0 + HEALTH_BAR_HEIGHT);
+        self.t.flush();
+    }
+
+    fn render_crosshair(&mut self, x: i32, y: i32) {
+        unsafe {
+            glColor4f(1.0, 1.0, 1.0, 1.0);
+        }
+        self.t.init();
+        fill(&mut self.t, x, y - 4, x + 1, y + 5);
+        fill(&mut self.t, x - 4, y, x + 5, y + 1);
+        self.t.flush();
+    }
+}
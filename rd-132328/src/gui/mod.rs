@@ -0,0 +1,3 @@
+pub mod font;
+pub mod hotbar;
+pub mod hud;
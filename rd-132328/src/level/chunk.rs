@@ -17,6 +17,9 @@ lazy_static! {
     static ref TESSELATOR: Mutex<Tesselator> = Mutex::new(Tesselator::new());
 }
 
+/// Display lists per chunk: lit faces, faces in shadow and translucent faces.
+const LAYERS: i32 = 3;
+
 pub static REBUILT_THIS_FRAME: AtomicI32 = AtomicI32::new(0);
 pub static UPDATES: AtomicI32 = AtomicI32::new(0);
 
@@ -56,12 +59,12 @@ impl Chunk {
                 y1,
                 z1,
                 dirty: true,
-                lists: glGenLists(2) as i32,
+                lists: glGenLists(LAYERS) as i32,
             }
         }
     }
 
-    fn rebuild(&mut self, layer: i32) {
+    fn rebuild(&mut self) {
         if REBUILT_THIS_FRAME.load(Ordering::SeqCst) == 2 {
             return;
         }
@@ -71,7 +74,20 @@ impl Chunk {
             REBUILT_THIS_FRAME.load(Ordering::SeqCst) + 1,
             Ordering::SeqCst,
         );
+        for layer in 0..LAYERS {
+            self.compile(layer);
+        }
+    }
+
+    /// Rebuilds all layers right away, ignoring the per-frame rebuild limit.
+    pub fn rebuild_now(&mut self) {
+        self.dirty = false;
+        for layer in 0..LAYERS {
+            self.compile(layer);
+        }
+    }
 
+    fn compile(&mut self, layer: i32) {
         let id = load_texture("terrain.png", GL_NEAREST as i32);
 
         unsafe {
@@ -80,30 +96,12 @@ impl Chunk {
             glBindTexture(GL_TEXTURE_2D, id as GLuint);
         }
         TESSELATOR.lock().unwrap().init();
+        let level = self.level.borrow();
         for x in self.x0..self.x1 {
             for y in self.y0..self.y1 {
                 for z in self.z0..self.z1 {
-                    if self.level.borrow().is_tile(x, y, z) {
-                        let tex = y != self.level.borrow().depth * 2 / 3;
-                        if !tex {
-                            tile::ROCK.lock().unwrap().render(
-                                &mut TESSELATOR.lock().unwrap(),
-                                &self.level.borrow(),
-                                layer,
-                                x,
-                                y,
-                                z,
-                            );
-                        } else {
-                            tile::GRASS.lock().unwrap().render(
-                                &mut TESSELATOR.lock().unwrap(),
-                                &self.level.borrow(),
-                                layer,
-                                x,
-                                y,
-                                z,
-                            );
-                        }
+                    if let Some(tile) = tile::by_id(level.get_tile(x, y, z)) {
+                        tile.render(&mut TESSELATOR.lock().unwrap(), &level, layer, x, y, z);
                     }
                 }
             }
@@ -117,15 +115,26 @@ impl Chunk {
 
     pub fn render(&mut self, layer: i32) {
         if self.dirty {
-            self.rebuild(0);
-            self.rebuild(1);
+            self.rebuild();
         }
         unsafe {
             glCallList((self.lists + layer) as GLuint);
         }
     }
 
+    pub fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
     pub fn set_dirty(&mut self) {
         self.dirty = true;
     }
 }
+
+impl Drop for Chunk {
+    fn drop(&mut self) {
+        unsafe {
+            glDeleteLists(self.lists as GLuint, LAYERS);
+        }
+    }
+}
@@ -0,0 +1,96 @@
+use super::tile;
+
+pub const COLUMN_SIZE: i32 = 16;
+
+pub struct Column {
+    blocks: Vec<u8>,
+    data: Vec<u8>, // per tile, e.g. the level of a fluid
+    light_depths: Vec<i32>,
+    fluid_depths: Vec<i32>, // topmost fluid or light blocker
+}
+
+impl Column {
+    pub fn new(blocks: Vec<u8>) -> Column {
+        let data = vec![0; blocks.len()];
+        Column::with_data(blocks, data)
+    }
+
+    pub fn with_data(blocks: Vec<u8>, data: Vec<u8>) -> Column {
+        Column {
+            blocks,
+            data,
+            light_depths: vec![0; (COLUMN_SIZE * COLUMN_SIZE) as usize],
+            fluid_depths: vec![0; (COLUMN_SIZE * COLUMN_SIZE) as usize],
+        }
+    }
+
+    /// Reads a column saved by `to_bytes`.
+    pub fn from_bytes(bytes: &[u8], depth: i32) -> Column {
+        let len = (COLUMN_SIZE * COLUMN_SIZE * depth) as usize;
+        if bytes.len() == len * 2 {
+            Column::with_data(bytes[..len].to_vec(), bytes[len..].to_vec())
+        } else {
+            // saved before tiles had data
+            Column::new(bytes.to_vec())
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        [&self.blocks[..], &self.data[..]].concat()
+    }
+
+    pub fn generate(depth: i32) -> Column {
+        let mut blocks = vec![0u8; (COLUMN_SIZE * COLUMN_SIZE * depth) as usize];
+        let surface = depth * 2 / 3;
+        for x in 0..COLUMN_SIZE {
+            for y in 0..depth {
+                for z in 0..COLUMN_SIZE {
+                    blocks[Self::index(x, y, z)] = if y < surface {
+                        tile::ROCK.id as u8
+                    } else if y == surface {
+                        tile::GRASS.id as u8
+                    } else {
+                        0
+                    };
+                }
+            }
+        }
+        Column::new(blocks)
+    }
+
+    fn index(x: i32, y: i32, z: i32) -> usize {
+        ((y * COLUMN_SIZE + z) * COLUMN_SIZE + x) as usize
+    }
+
+    pub fn get_tile(&self, x: i32, y: i32, z: i32) -> u8 {
+        self.blocks[Self::index(x, y, z)]
+    }
+
+    pub fn set_tile(&mut self, x: i32, y: i32, z: i32, tile_type: u8) {
+        self.blocks[Self::index(x, y, z)] = tile_type;
+    }
+
+    pub fn get_data(&self, x: i32, y: i32, z: i32) -> u8 {
+        self.data[Self::index(x, y, z)]
+    }
+
+    pub fn set_data(&mut self, x: i32, y: i32, z: i32, data: u8) {
+        self.data[Self::index(x, y, z)] = data;
+    }
+
+    pub fn light_depth(&self, x: i32, z: i32) -> i32 {
+        self.light_depths[(x + z * COLUMN_SIZE) as usize]
+    }
+
+    pub fn set_light_depth(&mut self, x: i32, z: i32, depth: i32) {
+        self.light_depths[(x + z * COLUMN_SIZE) as usize] = depth;
+    }
+
+    pub fn fluid_depth(&self, x: i32, z: i32) -> i32 {
+        self.fluid_depths[(x + z * COLUMN_SIZE) as usize]
+    }
+
+    pub fn set_fluid_depth(&mut self, x: i32, z: i32, depth: i32) {
+        self.fluid_depths[(x + z * COLUMN_SIZE) as usize] = depth;
+    }
+}
@@ -0,0 +1,126 @@
+use std::collections::HashMap;
+
+use crate::phys::aabb::AABB;
+
+use super::level::EntityId;
+
+/// Width of the grid cells in blocks, around the size of a few mobs standing together.
+const CELL_SIZE: f32 = 4.0;
+
+type Cells = ((i32, i32, i32), (i32, i32, i32));
+
+/// Where the entities in the level are, bucketed into a uniform grid so that finding the ones
+/// in an area only looks at the cells around it.
+pub struct EntityGrid {
+    cells: HashMap<(i32, i32, i32), Vec<EntityId>>,
+    bbs: HashMap<EntityId, AABB>,
+}
+
+impl EntityGrid {
+    pub fn new() -> EntityGrid {
+        EntityGrid {
+            cells: HashMap::new(),
+            bbs: HashMap::new(),
+        }
+    }
+
+    pub fn bb(&self, id: EntityId) -> Option<&AABB> {
+        self.bbs.get(&id)
+    }
+
+    /// Adds the entity with `id` at `bb`, or moves it there if it is already in the grid.
+    pub fn insert(&mut self, id: EntityId, bb: AABB) {
+        let cells = Self::cells(&bb);
+        if let Some(old) = self.bbs.insert(id, bb) {
+            let old = Self::cells(&old);
+            if old == cells {
+                return;
+            }
+            self.remove_from_cells(id, old);
+        }
+        for_cells(cells, |cell| self.cells.entry(cell).or_default().push(id));
+    }
+
+    pub fn remove(&mut self, id: EntityId) {
+        if let Some(bb) = self.bbs.remove(&id) {
+            self.remove_from_cells(id, Self::cells(&bb));
+        }
+    }
+
+    /// The entities whose bounding box overlaps `aabb`, by id.
+    pub fn query(&self, aabb: &AABB) -> Vec<EntityId> {
+        let mut ids = vec![];
+        for_cells(Self::cells(aabb), |cell| {
+            if let Some(cell) = self.cells.get(&cell) {
+                ids.extend(cell.iter().filter(|id| self.bbs[*id].intersects(aabb)));
+            }
+        });
+        ids.sort_unstable();
+        ids.dedup();
+        ids
+    }
+
+    fn remove_from_cells(&mut self, id: EntityId, cells: Cells) {
+        for_cells(cells, |cell| {
+            if let Some(ids) = self.cells.get_mut(&cell) {
+                ids.retain(|i| *i != id);
+                if ids.is_empty() {
+                    self.cells.remove(&cell);
+                }
+            }
+        });
+    }
+
+    /// The first and last cell `bb` touches.
+    fn cells(bb: &AABB) -> Cells {
+        let cell = |a: f32| (a / CELL_SIZE).floor() as i32;
+        (
+            (cell(bb.x0), cell(bb.y0), cell(bb.z0)),
+            (cell(bb.x1), cell(bb.y1), cell(bb.z1)),
+        )
+    }
+}
+
+fn for_cells(((x0, y0, z0), (x1, y1, z1)): Cells, mut f: impl FnMut((i32, i32, i32))) {
+    for x in x0..=x1 {
+        for y in y0..=y1 {
+            for z in z0..=z1 {
+                f((x, y, z));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mob_at(x: f32, y: f32, z: f32) -> AABB {
+        AABB::new(x - 0.3, y, z - 0.3, x + 0.3, y + 1.8, z + 0.3)
+    }
+
+    #[test]
+    fn finds_entities_overlapping_an_area() {
+        let mut grid = EntityGrid::new();
+        grid.insert(1, mob_at(0.5, 0.0, 0.5));
+        grid.insert(2, mob_at(3.9, 0.0, 0.5)); // across a cell border
+        grid.insert(3, mob_at(40.0, 0.0, 0.5));
+        assert_eq!(grid.query(&AABB::new(0.0, 0.0, 0.0, 5.0, 1.0, 1.0)), [1, 2]);
+        assert_eq!(grid.query(&AABB::new(3.0, 0.0, 0.0, 4.0, 1.0, 1.0)), [2]);
+        assert!(grid
+            .query(&AABB::new(10.0, 0.0, 0.0, 11.0, 1.0, 1.0))
+            .is_empty());
+    }
+
+    #[test]
+    fn follows_entities_as_they_move() {
+        let mut grid = EntityGrid::new();
+        grid.insert(1, mob_at(0.5, 0.0, 0.5));
+        grid.insert(1, mob_at(20.5, 0.0, 0.5));
+        assert!(grid.query(&mob_at(0.5, 0.0, 0.5)).is_empty());
+        assert_eq!(grid.query(&mob_at(20.5, 0.0, 0.5)), [1]);
+        grid.remove(1);
+        assert!(grid.query(&mob_at(20.5, 0.0, 0.5)).is_empty());
+        assert!(grid.cells.is_empty());
+    }
+}
@@ -0,0 +1,214 @@
+use super::level::Level;
+use super::tile;
+
+/// Flowing fluid runs out after this level, sources are level 0.
+pub const MAX_LEVEL: i32 = 7;
+
+const NEIGHBORS: [(i32, i32, i32); 4] = [(-1, 0, 0), (1, 0, 0), (0, 0, -1), (0, 0, 1)];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fluid {
+    Water,
+    Lava,
+}
+
+impl Fluid {
+    /// The fluid stored under tile `id`, if it is one.
+    pub fn of(id: i32) -> Option<Fluid> {
+        match tile::by_id(id) {
+            Some(tile) => tile.fluid(),
+            None => None,
+        }
+    }
+
+    /// Ticks between a change next to the fluid and it flowing on.
+    pub fn tick_delay(self) -> u64 {
+        match self {
+            Fluid::Water => 5,
+            Fluid::Lava => 30,
+        }
+    }
+
+    /// How much the level rises with each block the fluid flows sideways.
+    fn spread_step(self) -> i32 {
+        match self {
+            Fluid::Water => 1,
+            Fluid::Lava => 2,
+        }
+    }
+
+    /// Share of an entity's motion that is kept each tick while it is inside the fluid.
+    pub fn drag(self) -> f32 {
+        match self {
+            Fluid::Water => 0.8,
+            Fluid::Lava => 0.5,
+        }
+    }
+
+    /// Upwards push on an entity inside the fluid, a little less than gravity.
+    pub fn buoyancy(self) -> f32 {
+        match self {
+            Fluid::Water => 0.003,
+            Fluid::Lava => 0.002,
+        }
+    }
+
+    /// Height of the fluid's surface within its tile.
+    pub fn height(level: i32) -> f32 {
+        1.0 - (level + 1) as f32 / (MAX_LEVEL + 2) as f32
+    }
+}
+
+/// Lets the fluid at `x`/`y`/`z` flow on: it dries up without anything feeding it, falls into
+/// empty space below and otherwise spreads sideways until it reaches `MAX_LEVEL`.
+pub fn update(level: &mut Level, x: i32, y: i32, z: i32) {
+    let id = level.get_tile(x, y, z);
+    let fluid = match Fluid::of(id) {
+        Some(fluid) => fluid,
+        None => return,
+    };
+    let mut depth = level.get_data(x, y, z);
+
+    if fluid == Fluid::Lava && touches(level, x, y, z, Fluid::Water) {
+        let id = if depth == 0 {
+            tile::ROCK.id
+        } else {
+            tile::COBBLESTONE.id
+        };
+        level.set_tile(x, y, z, id);
+        return;
+    }
+
+    if depth > 0 {
+        let fed = if Fluid::of(level.get_tile(x, y + 1, z)) == Some(fluid) {
+            Some(1)
+        } else {
+            NEIGHBORS
+                .iter()
+                .filter(|(xd, _, zd)| Fluid::of(level.get_tile(x + xd, y, z + zd)) == Some(fluid))
+                .map(|(xd, _, zd)| level.get_data(x + xd, y, z + zd) + fluid.spread_step())
+                .min()
+        };
+        match fed {
+            Some(d) if d <= MAX_LEVEL => {
+                if d != depth {
+                    level.set_tile_with_data(x, y, z, id, d);
+                    depth = d;
+                }
+            }
+            _ => {
+                level.set_tile(x, y, z, 0);
+                return;
+            }
+        }
+    }
+
+    if can_flow_into(level, x, y - 1, z, fluid, 1) {
+        level.set_tile_with_data(x, y - 1, z, id, 1);
+        return;
+    }
+    let spread = depth + fluid.spread_step();
+    if spread > MAX_LEVEL || Fluid::of(level.get_tile(x, y - 1, z)) == Some(fluid) {
+        return;
+    }
+    for (xd, _, zd) in NEIGHBORS {
+        if can_flow_into(level, x + xd, y, z + zd, fluid, spread) {
+            level.set_tile_with_data(x + xd, y, z + zd, id, spread);
+        }
+    }
+}
+
+fn touches(level: &Level, x: i32, y: i32, z: i32, fluid: Fluid) -> bool {
+    NEIGHBORS
+        .iter()
+        .chain(&[(0, -1, 0), (0, 1, 0)])
+        .any(|(xd, yd, zd)| Fluid::of(level.get_tile(x + xd, y + yd, z + zd)) == Some(fluid))
+}
+
+fn can_flow_into(level: &Level, x: i32, y: i32, z: i32, fluid: Fluid, depth: i32) -> bool {
+    if y < 0 || !level.is_loaded(x, z) {
+        return false;
+    }
+    match level.get_tile(x, y, z) {
+        0 => true,
+        id => Fluid::of(id) == Some(fluid) && level.get_data(x, y, z) > depth,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn flat_level() -> (Level, i32) {
+        let mut level = Level::with_dir(64, None);
+        level.update_columns(0.0, 0.0);
+        let ground = level.depth * 2 / 3 + 1;
+        (level, ground)
+    }
+
+    fn run(level: &mut Level, ticks: i32) {
+        for _ in 0..ticks {
+            level.tick();
+        }
+    }
+
+    #[test]
+    fn water_spreads_until_max_level() {
+        let (mut level, y) = flat_level();
+        level.set_tile(0, y, 0, tile::WATER.id);
+        run(&mut level, 100);
+        for d in 1..=MAX_LEVEL {
+            assert_eq!(level.get_tile(d, y, 0), tile::WATER.id);
+            assert_eq!(level.get_data(d, y, 0), d);
+        }
+        assert_eq!(level.get_tile(MAX_LEVEL + 1, y, 0), 0);
+        assert_eq!(level.get_tile(0, y + 1, 0), 0);
+    }
+
+    #[test]
+    fn flowing_water_dries_up_without_a_source() {
+        let (mut level, y) = flat_level();
+        level.set_tile(0, y, 0, tile::WATER.id);
+        run(&mut level, 100);
+        level.set_tile(0, y, 0, 0);
+        run(&mut level, 100);
+        for x in -MAX_LEVEL..=MAX_LEVEL {
+            assert_eq!(level.get_tile(x, y, 0), 0);
+        }
+    }
+
+    #[test]
+    fn water_falls_down() {
+        let (mut level, y) = flat_level();
+        level.set_tile(0, y + 3, 0, tile::WATER.id);
+        run(&mut level, 100);
+        assert_eq!(level.get_tile(0, y, 0), tile::WATER.id);
+        assert_eq!(level.get_tile(1, y + 2, 0), 0);
+        assert_eq!(level.get_tile(1, y, 0), tile::WATER.id);
+    }
+
+    #[test]
+    fn lava_meeting_water_hardens() {
+        let (mut level, y) = flat_level();
+        level.set_tile(0, y, 0, tile::LAVA.id);
+        level.set_tile(4, y, 0, tile::WATER.id);
+        run(&mut level, 200);
+        let hardened = [tile::ROCK.id, tile::COBBLESTONE.id];
+        assert!((0..4).any(|x| hardened.contains(&level.get_tile(x, y, 0))));
+        for x in -8..8 {
+            let pair = (level.get_tile(x, y, 0), level.get_tile(x + 1, y, 0));
+            assert_ne!(pair, (tile::LAVA.id, tile::WATER.id));
+            assert_ne!(pair, (tile::WATER.id, tile::LAVA.id));
+        }
+    }
+
+    #[test]
+    fn fluid_dims_the_light_below() {
+        let (mut level, y) = flat_level();
+        assert_eq!(level.get_brightness(0, y, 0), 1.0);
+        level.set_tile(0, y + 1, 0, tile::WATER.id);
+        assert_eq!(level.get_brightness(0, y, 0), 0.9);
+        level.set_tile(0, y + 1, 0, tile::ROCK.id);
+        assert_eq!(level.get_brightness(0, y, 0), 0.8);
+    }
+}
@@ -1,7 +1,3 @@
-use std::sync::Mutex;
-
-use crate::glu::*;
-
 use crate::phys::aabb::AABB;
 
 pub const RIGHT: usize = 0;
@@ -15,34 +11,18 @@ pub const B: usize = 1;
 pub const C: usize = 2;
 pub const D: usize = 3;
 
-static FRUSTUM: Mutex<Frustum> = Mutex::new(Frustum::new());
-
 pub struct Frustum {
     pub m_frustum: [[f32; 4]; 6],
-    _proj: [GLfloat; 16],
-    _modl: [GLfloat; 16],
-    _clip: [GLfloat; 16],
-    proj: [f32; 16],
-    modl: [f32; 16],
-    clip: [f32; 16],
 }
 
 impl Frustum {
-    const fn new() -> Frustum {
-        Frustum {
+    /// Derives the clipping planes from column-major projection and modelview matrices.
+    pub fn new(proj: &[f32; 16], modl: &[f32; 16]) -> Frustum {
+        let mut frustum = Frustum {
             m_frustum: [[0.0; 4]; 6],
-            _proj: [0.0; 16],
-            _modl: [0.0; 16],
-            _clip: [0.0; 16],
-            proj: [0.0; 16],
-            modl: [0.0; 16],
-            clip: [0.0; 16],
-        }
-    }
-
-    pub fn get_frustum() -> &'static Mutex<Frustum> {
-        FRUSTUM.lock().unwrap().calculate_frustum();
-        &FRUSTUM
+        };
+        frustum.calculate_frustum(proj, modl);
+        frustum
     }
 
     fn normalize_plane(frust: &mut [[f32; 4]; 6], side: usize) {
@@ -57,113 +37,60 @@ impl Frustum {
         f_array[D] /= magnitude;
     }
 
-    fn calculate_frustum(&mut self) {
-        for i in 0..16 {
-            self._proj[i] = 0.0;
-            self._modl[i] = 0.0;
-            self._clip[i] = 0.0;
-        }
-        unsafe {
-            glGetFloatv(GL_PROJECTION_MATRIX, self._proj.as_mut_ptr());
-            glGetFloatv(GL_MODELVIEW_MATRIX, self._modl.as_mut_ptr());
-        }
-
-        self.proj = self._proj;
-        self.modl = self._modl;
+    fn calculate_frustum(&mut self, proj: &[f32; 16], modl: &[f32; 16]) {
+        let mut clip = [0.0; 16];
 
-        self.clip[0] = self.modl[0] * self.proj[0]
-            + self.modl[1] * self.proj[4]
-            + self.modl[2] * self.proj[8]
-            + self.modl[3] * self.proj[12];
-        self.clip[1] = self.modl[0] * self.proj[1]
-            + self.modl[1] * self.proj[5]
-            + self.modl[2] * self.proj[9]
-            + self.modl[3] * self.proj[13];
-        self.clip[2] = self.modl[0] * self.proj[2]
-            + self.modl[1] * self.proj[6]
-            + self.modl[2] * self.proj[10]
-            + self.modl[3] * self.proj[14];
-        self.clip[3] = self.modl[0] * self.proj[3]
-            + self.modl[1] * self.proj[7]
-            + self.modl[2] * self.proj[11]
-            + self.modl[3] * self.proj[15];
-        self.clip[4] = self.modl[4] * self.proj[0]
-            + self.modl[5] * self.proj[4]
-            + self.modl[6] * self.proj[8]
-            + self.modl[7] * self.proj[12];
-        self.clip[5] = self.modl[4] * self.proj[1]
-            + self.modl[5] * self.proj[5]
-            + self.modl[6] * self.proj[9]
-            + self.modl[7] * self.proj[13];
-        self.clip[6] = self.modl[4] * self.proj[2]
-            + self.modl[5] * self.proj[6]
-            + self.modl[6] * self.proj[10]
-            + self.modl[7] * self.proj[14];
-        self.clip[7] = self.modl[4] * self.proj[3]
-            + self.modl[5] * self.proj[7]
-            + self.modl[6] * self.proj[11]
-            + self.modl[7] * self.proj[15];
-        self.clip[8] = self.modl[8] * self.proj[0]
-            + self.modl[9] * self.proj[4]
-            + self.modl[10] * self.proj[8]
-            + self.modl[11] * self.proj[12];
-        self.clip[9] = self.modl[8] * self.proj[1]
-            + self.modl[9] * self.proj[5]
-            + self.modl[10] * self.proj[9]
-            + self.modl[11] * self.proj[13];
-        self.clip[10] = self.modl[8] * self.proj[2]
-            + self.modl[9] * self.proj[6]
-            + self.modl[10] * self.proj[10]
-            + self.modl[11] * self.proj[14];
-        self.clip[11] = self.modl[8] * self.proj[3]
-            + self.modl[9] * self.proj[7]
-            + self.modl[10] * self.proj[11]
-            + self.modl[11] * self.proj[15];
-        self.clip[12] = self.modl[12] * self.proj[0]
-            + self.modl[13] * self.proj[4]
-            + self.modl[14] * self.proj[8]
-            + self.modl[15] * self.proj[12];
-        self.clip[13] = self.modl[12] * self.proj[1]
-            + self.modl[13] * self.proj[5]
-            + self.modl[14] * self.proj[9]
-            + self.modl[15] * self.proj[13];
-        self.clip[14] = self.modl[12] * self.proj[2]
-            + self.modl[13] * self.proj[6]
-            + self.modl[14] * self.proj[10]
-            + self.modl[15] * self.proj[14];
-        self.clip[15] = self.modl[12] * self.proj[3]
-            + self.modl[13] * self.proj[7]
-            + self.modl[14] * self.proj[11]
-            + self.modl[15] * self.proj[15];
-        self.m_frustum[RIGHT][A] = self.clip[3] - self.clip[0];
-        self.m_frustum[RIGHT][B] = self.clip[7] - self.clip[4];
-        self.m_frustum[RIGHT][C] = self.clip[11] - self.clip[8];
-        self.m_frustum[RIGHT][D] = self.clip[15] - self.clip[12];
+        clip[0] = modl[0] * proj[0] + modl[1] * proj[4] + modl[2] * proj[8] + modl[3] * proj[12];
+        clip[1] = modl[0] * proj[1] + modl[1] * proj[5] + modl[2] * proj[9] + modl[3] * proj[13];
+        clip[2] = modl[0] * proj[2] + modl[1] * proj[6] + modl[2] * proj[10] + modl[3] * proj[14];
+        clip[3] = modl[0] * proj[3] + modl[1] * proj[7] + modl[2] * proj[11] + modl[3] * proj[15];
+        clip[4] = modl[4] * proj[0] + modl[5] * proj[4] + modl[6] * proj[8] + modl[7] * proj[12];
+        clip[5] = modl[4] * proj[1] + modl[5] * proj[5] + modl[6] * proj[9] + modl[7] * proj[13];
+        clip[6] = modl[4] * proj[2] + modl[5] * proj[6] + modl[6] * proj[10] + modl[7] * proj[14];
+        clip[7] = modl[4] * proj[3] + modl[5] * proj[7] + modl[6] * proj[11] + modl[7] * proj[15];
+        clip[8] = modl[8] * proj[0] + modl[9] * proj[4] + modl[10] * proj[8] + modl[11] * proj[12];
+        clip[9] = modl[8] * proj[1] + modl[9] * proj[5] + modl[10] * proj[9] + modl[11] * proj[13];
+        clip[10] =
+            modl[8] * proj[2] + modl[9] * proj[6] + modl[10] * proj[10] + modl[11] * proj[14];
+        clip[11] =
+            modl[8] * proj[3] + modl[9] * proj[7] + modl[10] * proj[11] + modl[11] * proj[15];
+        clip[12] =
+            modl[12] * proj[0] + modl[13] * proj[4] + modl[14] * proj[8] + modl[15] * proj[12];
+        clip[13] =
+            modl[12] * proj[1] + modl[13] * proj[5] + modl[14] * proj[9] + modl[15] * proj[13];
+        clip[14] =
+            modl[12] * proj[2] + modl[13] * proj[6] + modl[14] * proj[10] + modl[15] * proj[14];
+        clip[15] =
+            modl[12] * proj[3] + modl[13] * proj[7] + modl[14] * proj[11] + modl[15] * proj[15];
+        self.m_frustum[RIGHT][A] = clip[3] - clip[0];
+        self.m_frustum[RIGHT][B] = clip[7] - clip[4];
+        self.m_frustum[RIGHT][C] = clip[11] - clip[8];
+        self.m_frustum[RIGHT][D] = clip[15] - clip[12];
         Self::normalize_plane(&mut self.m_frustum, RIGHT);
-        self.m_frustum[LEFT][A] = self.clip[3] + self.clip[0];
-        self.m_frustum[LEFT][B] = self.clip[7] + self.clip[4];
-        self.m_frustum[LEFT][C] = self.clip[11] + self.clip[8];
-        self.m_frustum[LEFT][D] = self.clip[15] + self.clip[12];
+        self.m_frustum[LEFT][A] = clip[3] + clip[0];
+        self.m_frustum[LEFT][B] = clip[7] + clip[4];
+        self.m_frustum[LEFT][C] = clip[11] + clip[8];
+        self.m_frustum[LEFT][D] = clip[15] + clip[12];
         Self::normalize_plane(&mut self.m_frustum, LEFT);
-        self.m_frustum[BOTTOM][A] = self.clip[3] + self.clip[1];
-        self.m_frustum[BOTTOM][B] = self.clip[7] + self.clip[5];
-        self.m_frustum[BOTTOM][C] = self.clip[11] + self.clip[9];
-        self.m_frustum[BOTTOM][D] = self.clip[15] + self.clip[13];
+        self.m_frustum[BOTTOM][A] = clip[3] + clip[1];
+        self.m_frustum[BOTTOM][B] = clip[7] + clip[5];
+        self.m_frustum[BOTTOM][C] = clip[11] + clip[9];
+        self.m_frustum[BOTTOM][D] = clip[15] + clip[13];
         Self::normalize_plane(&mut self.m_frustum, BOTTOM);
-        self.m_frustum[TOP][A] = self.clip[3] - self.clip[1];
-        self.m_frustum[TOP][B] = self.clip[7] - self.clip[5];
-        self.m_frustum[TOP][C] = self.clip[11] - self.clip[9];
-        self.m_frustum[TOP][D] = self.clip[15] - self.clip[13];
+        self.m_frustum[TOP][A] = clip[3] - clip[1];
+        self.m_frustum[TOP][B] = clip[7] - clip[5];
+        self.m_frustum[TOP][C] = clip[11] - clip[9];
+        self.m_frustum[TOP][D] = clip[15] - clip[13];
         Self::normalize_plane(&mut self.m_frustum, TOP);
-        self.m_frustum[BACK][A] = self.clip[3] - self.clip[2];
-        self.m_frustum[BACK][B] = self.clip[7] - self.clip[6];
-        self.m_frustum[BACK][C] = self.clip[11] - self.clip[10];
-        self.m_frustum[BACK][D] = self.clip[15] - self.clip[14];
+        self.m_frustum[BACK][A] = clip[3] - clip[2];
+        self.m_frustum[BACK][B] = clip[7] - clip[6];
+        self.m_frustum[BACK][C] = clip[11] - clip[10];
+        self.m_frustum[BACK][D] = clip[15] - clip[14];
         Self::normalize_plane(&mut self.m_frustum, BACK);
-        self.m_frustum[FRONT][A] = self.clip[3] + self.clip[2];
-        self.m_frustum[FRONT][B] = self.clip[7] + self.clip[6];
-        self.m_frustum[FRONT][C] = self.clip[11] + self.clip[10];
-        self.m_frustum[FRONT][D] = self.clip[15] + self.clip[14];
+        self.m_frustum[FRONT][A] = clip[3] + clip[2];
+        self.m_frustum[FRONT][B] = clip[7] + clip[6];
+        self.m_frustum[FRONT][C] = clip[11] + clip[10];
+        self.m_frustum[FRONT][D] = clip[15] + clip[14];
         Self::normalize_plane(&mut self.m_frustum, FRONT);
     }
 
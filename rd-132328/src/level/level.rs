@@ -1,84 +1,242 @@
-use std::fs::File;
-use std::io::prelude::*;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::path::{Path, PathBuf};
 use std::{cell::RefCell, rc::Rc};
 
-use flate2::bufread::GzDecoder;
-use flate2::write::GzEncoder;
-use flate2::Compression;
+use rand::Rng;
 
+use crate::entity::EntityTrait;
 use crate::phys::aabb::AABB;
 
+use super::column::{Column, COLUMN_SIZE};
+use super::entity_grid::EntityGrid;
+use super::fluid::Fluid;
 use super::level_listener::LevelListener;
+use super::region::{Region, REGION_SIZE};
+use super::tile;
+
+/// Columns within this many columns of the player are loaded or generated.
+const LOAD_RADIUS: i32 = 8;
+/// Columns further than this many columns from the player are saved and unloaded.
+const UNLOAD_RADIUS: i32 = 10;
+/// Tiles picked at random from every 16 tile high part of a column each tick.
+const RANDOM_TICKS: i32 = 3;
+/// Entities (re)spawn at random within this many blocks of the origin.
+pub const SPAWN_SIZE: i32 = 256;
+
+/// Identifies an entity in the level for as long as it is in there, ids are never reused.
+pub type EntityId = u64;
+
+/// The region the column at `x`/`z` is saved in.
+fn region_of(x: i32, z: i32) -> (i32, i32) {
+    (x.div_euclid(REGION_SIZE), z.div_euclid(REGION_SIZE))
+}
 
 pub struct Level {
-    pub width: i32,
-    pub height: i32,
     pub depth: i32,
-    blocks: Vec<u8>,
-    light_depths: Vec<i32>,
+    pub collision_epsilon: f32, // gap kept between entities and the tiles they collide with
+    columns: HashMap<(i32, i32), Column>,
+    regions: HashMap<(i32, i32), Region>,
+    dir: Option<PathBuf>, // where the regions are saved, none to never touch the disk
     level_listeners: Vec<Rc<RefCell<dyn LevelListener>>>,
+    ticks: u64,
+    scheduled: BTreeSet<(u64, i32, i32, i32)>, // tile updates, by the tick they are due
+    scheduled_tiles: HashSet<(i32, i32, i32)>, // the tiles in `scheduled`
+    falling: Vec<(i32, i32, i32, i32)>,        // tiles that started to fall, and their ids
+    entities: BTreeMap<EntityId, Rc<RefCell<dyn EntityTrait>>>,
+    next_entity_id: EntityId,
+    entity_grid: EntityGrid, // where the entities were after they last moved
+    pub player_bb: Option<AABB>, // where the player is, for mobs to go after
+    player_hits: Vec<(i32, f32, f32)>, // damage mobs did to the player, and where from
 }
 
 impl Level {
-    pub fn new(w: i32, h: i32, d: i32) -> Level {
-        let mut level = Level {
-            width: w,
-            height: h,
+    pub fn new(d: i32) -> Level {
+        Level::with_dir(d, Some(PathBuf::from("world")))
+    }
+
+    /// A level saved in `dir`, or never saved and always freshly generated without one.
+    pub fn with_dir(d: i32, dir: Option<PathBuf>) -> Level {
+        Level {
             depth: d,
-            blocks: vec![0u8; (w * h * d) as usize],
-            light_depths: vec![0; (w * h) as usize],
+            collision_epsilon: 0.0,
+            columns: HashMap::new(),
+            regions: HashMap::new(),
+            dir,
             level_listeners: vec![],
-        };
+            ticks: 0,
+            scheduled: BTreeSet::new(),
+            scheduled_tiles: HashSet::new(),
+            falling: vec![],
+            entities: BTreeMap::new(),
+            next_entity_id: 0,
+            entity_grid: EntityGrid::new(),
+            player_bb: None,
+            player_hits: vec![],
+        }
+    }
 
-        for x in 0..w {
-            for y in 0..d {
-                for z in 0..h {
-                    let i = (y * h + z) * w + x;
-                    level.blocks[i as usize] = if y <= d * 2 / 3 { 1 } else { 0 };
-                }
+    /// Where the level is saved, if anywhere.
+    pub fn dir(&self) -> Option<&Path> {
+        self.dir.as_deref()
+    }
+
+    fn column_at(&self, x: i32, z: i32) -> Option<&Column> {
+        self.columns
+            .get(&(x.div_euclid(COLUMN_SIZE), z.div_euclid(COLUMN_SIZE)))
+    }
+
+    fn column_at_mut(&mut self, x: i32, z: i32) -> Option<&mut Column> {
+        self.columns
+            .get_mut(&(x.div_euclid(COLUMN_SIZE), z.div_euclid(COLUMN_SIZE)))
+    }
+
+    fn region(&mut self, x: i32, z: i32) -> Option<&mut Region> {
+        let (rx, rz) = region_of(x, z);
+        let dir = self.dir.as_ref()?;
+        Some(
+            self.regions
+                .entry((rx, rz))
+                .or_insert_with(|| Region::open(dir, rx, rz)),
+        )
+    }
+
+    pub fn is_loaded(&self, x: i32, z: i32) -> bool {
+        self.column_at(x, z).is_some()
+    }
+
+    pub fn loaded_columns(&self) -> Vec<(i32, i32)> {
+        self.columns.keys().copied().collect()
+    }
+
+    /// Loads or generates the columns around `x`/`z` and unloads the ones that are too far away.
+    pub fn update_columns(&mut self, x: f32, z: f32) {
+        let cx = (x.floor() as i32).div_euclid(COLUMN_SIZE);
+        let cz = (z.floor() as i32).div_euclid(COLUMN_SIZE);
+
+        let far: Vec<(i32, i32)> = self
+            .columns
+            .keys()
+            .filter(|(x, z)| (x - cx).pow(2) + (z - cz).pow(2) > UNLOAD_RADIUS * UNLOAD_RADIUS)
+            .copied()
+            .collect();
+        for (x, z) in &far {
+            self.unload_column(*x, *z);
+        }
+        if !far.is_empty() {
+            for region in self.regions.values_mut() {
+                region.save();
             }
         }
 
-        level.calc_light_depths(0, 0, w, h);
-        level.load();
+        for x in (cx - LOAD_RADIUS)..=(cx + LOAD_RADIUS) {
+            for z in (cz - LOAD_RADIUS)..=(cz + LOAD_RADIUS) {
+                if (x - cx).pow(2) + (z - cz).pow(2) <= LOAD_RADIUS * LOAD_RADIUS
+                    && !self.columns.contains_key(&(x, z))
+                {
+                    self.load_column(x, z);
+                }
+            }
+        }
+    }
 
-        level
+    fn load_column(&mut self, x: i32, z: i32) {
+        let depth = self.depth;
+        let column = match self.region(x, z).and_then(|region| region.get(x, z)) {
+            Some(bytes) => Column::from_bytes(bytes, depth),
+            None => Column::generate(depth),
+        };
+        // the updates of a column are dropped when it is unloaded, so tiles that were still
+        // falling or flowing then pick up again here
+        let mut updates = vec![];
+        for y in 0..depth {
+            for tz in 0..COLUMN_SIZE {
+                for tx in 0..COLUMN_SIZE {
+                    let id = column.get_tile(tx, y, tz) as i32;
+                    if let Some(delay) = tile::by_id(id).and_then(|tile| tile.update_delay()) {
+                        updates.push((x * COLUMN_SIZE + tx, y, z * COLUMN_SIZE + tz, delay));
+                    }
+                }
+            }
+        }
+        self.columns.insert((x, z), column);
+        for (x, y, z, delay) in updates {
+            self.schedule_update(x, y, z, delay);
+        }
+        self.calc_light_depths(x * COLUMN_SIZE, z * COLUMN_SIZE, COLUMN_SIZE, COLUMN_SIZE);
+        for level_listener in &self.level_listeners {
+            level_listener.borrow_mut().column_loaded(x, z);
+        }
     }
 
-    pub fn load(&mut self) {
-        if let Ok(file) = std::fs::read("level.dat") {
-            let mut gz = GzDecoder::new(&file[..]);
-            self.blocks.clear();
-            gz.read_to_end(&mut self.blocks).unwrap();
-            self.calc_light_depths(0, 0, self.width, self.height);
+    fn unload_column(&mut self, x: i32, z: i32) {
+        if let Some(column) = self.columns.remove(&(x, z)) {
+            if let Some(region) = self.region(x, z) {
+                region.put(x, z, &column.to_bytes());
+            }
+            let in_column = |tx: i32, tz: i32| {
+                (tx.div_euclid(COLUMN_SIZE), tz.div_euclid(COLUMN_SIZE)) == (x, z)
+            };
+            self.scheduled.retain(|&(_, tx, _, tz)| !in_column(tx, tz));
+            self.scheduled_tiles
+                .retain(|&(tx, _, tz)| !in_column(tx, tz));
+            self.evict_region(x, z);
             for level_listener in &self.level_listeners {
-                level_listener.borrow_mut().all_changed();
+                level_listener.borrow_mut().column_unloaded(x, z);
             }
         }
     }
 
-    pub fn save(&self) {
-        let w = File::create("level.dat").unwrap();
-        let mut e = GzEncoder::new(w, Compression::default());
-        e.write_all(&self.blocks).unwrap();
+    /// Saves and drops the region of the column at `x`/`z` once none of its columns are loaded.
+    fn evict_region(&mut self, x: i32, z: i32) {
+        let key = region_of(x, z);
+        if self.columns.keys().any(|&(x, z)| region_of(x, z) == key) {
+            return;
+        }
+        if let Some(mut region) = self.regions.remove(&key) {
+            region.save();
+        }
+    }
+
+    pub fn save(&mut self) {
+        for (x, z) in self.loaded_columns() {
+            let bytes = self.columns[&(x, z)].to_bytes();
+            if let Some(region) = self.region(x, z) {
+                region.put(x, z, &bytes);
+            }
+        }
+        for region in self.regions.values_mut() {
+            region.save();
+        }
     }
 
     pub fn calc_light_depths(&mut self, x0: i32, y0: i32, x1: i32, y1: i32) {
         for x in x0..(x0 + x1) {
             for z in y0..(y0 + y1) {
-                let old_depth = self.light_depths[(x + z * self.width) as usize];
+                let xl = x.rem_euclid(COLUMN_SIZE);
+                let zl = z.rem_euclid(COLUMN_SIZE);
+                let (old_depth, old_fluid_depth) = match self.column_at(x, z) {
+                    Some(column) => (column.light_depth(xl, zl), column.fluid_depth(xl, zl)),
+                    None => continue,
+                };
                 let mut y = self.depth - 1;
                 while y > 0 && !self.is_light_blocker(x, y, z) {
                     y -= 1;
                 }
-                self.light_depths[(x + z * self.width) as usize] = y;
-                if old_depth != y {
-                    let yl0 = if old_depth < y { old_depth } else { y };
-                    let yl1 = if old_depth > y { old_depth } else { y };
+                let mut fy = self.depth - 1;
+                while fy > y && Fluid::of(self.get_tile(x, fy, z)).is_none() {
+                    fy -= 1;
+                }
+                if let Some(column) = self.column_at_mut(x, z) {
+                    column.set_light_depth(xl, zl, y);
+                    column.set_fluid_depth(xl, zl, fy);
+                }
+                if old_depth != y || old_fluid_depth != fy {
+                    let yl0 = old_depth.min(y).min(old_fluid_depth).min(fy);
+                    let yl1 = old_depth.max(y).max(old_fluid_depth).max(fy);
                     for level_listener in &self.level_listeners {
                         level_listener
                             .borrow_mut()
-                            .light_column_changed(x, y, yl0, yl1);
+                            .light_column_changed(x, z, yl0, yl1);
                     }
                 }
             }
@@ -89,15 +247,37 @@ impl Level {
         self.level_listeners.push(level_listener);
     }
 
-    pub fn is_tile(&self, x: i32, y: i32, z: i32) -> bool {
-        if x < 0 || y < 0 || z < 0 || x >= self.width || y >= self.depth || z >= self.height {
-            return false;
+    pub fn get_tile(&self, x: i32, y: i32, z: i32) -> i32 {
+        if y < 0 || y >= self.depth {
+            return 0;
+        }
+        match self.column_at(x, z) {
+            Some(column) => {
+                column.get_tile(x.rem_euclid(COLUMN_SIZE), y, z.rem_euclid(COLUMN_SIZE)) as i32
+            }
+            None => 0,
+        }
+    }
+
+    pub fn remove_listener(&mut self, level_listener: &Rc<RefCell<dyn LevelListener>>) {
+        self.level_listeners
+            .retain(|l| !Rc::ptr_eq(l, level_listener));
+    }
+
+    pub fn get_data(&self, x: i32, y: i32, z: i32) -> i32 {
+        if y < 0 || y >= self.depth {
+            return 0;
+        }
+        match self.column_at(x, z) {
+            Some(column) => {
+                column.get_data(x.rem_euclid(COLUMN_SIZE), y, z.rem_euclid(COLUMN_SIZE)) as i32
+            }
+            None => 0,
         }
-        self.blocks[((y * self.height + z) * self.width + x) as usize] == 1
     }
 
     pub fn is_solid_tile(&self, x: i32, y: i32, z: i32) -> bool {
-        self.is_tile(x, y, z)
+        tile::by_id(self.get_tile(x, y, z)).is_some_and(|tile| tile.is_solid())
     }
 
     pub fn is_light_blocker(&self, x: i32, y: i32, z: i32) -> bool {
@@ -106,43 +286,34 @@ impl Level {
 
     pub fn get_cubes(&self, aabb: AABB) -> Vec<AABB> {
         let mut aabbs = vec![];
-        let mut x0 = aabb.x0 as i32;
-        let mut x1 = (aabb.x1 + 1.0) as i32;
+        let x0 = aabb.x0.floor() as i32;
+        let x1 = (aabb.x1 + 1.0).floor() as i32;
         let mut y0 = aabb.y0 as i32;
         let mut y1 = (aabb.y1 + 1.0) as i32;
-        let mut z0 = aabb.z0 as i32;
-        let mut z1 = (aabb.z1 + 1.0) as i32;
-        if x0 < 0 {
-            x0 = 0;
-        }
+        let z0 = aabb.z0.floor() as i32;
+        let z1 = (aabb.z1 + 1.0).floor() as i32;
         if y0 < 0 {
             y0 = 0;
         }
-        if z0 < 0 {
-            z0 = 0;
-        }
-        if x1 > self.width {
-            x1 = self.width;
-        }
         if y1 > self.depth {
             y1 = self.depth;
         }
-        if z1 > self.height {
-            z1 = self.height;
-        }
 
         for x in x0..x1 {
             for y in y0..y1 {
                 for z in z0..z1 {
                     if self.is_solid_tile(x, y, z) {
-                        aabbs.push(AABB::new(
-                            x as f32,
-                            y as f32,
-                            z as f32,
-                            (x + 1) as f32,
-                            (y + 1) as f32,
-                            (z + 1) as f32,
-                        ));
+                        aabbs.push(
+                            AABB::new(
+                                x as f32,
+                                y as f32,
+                                z as f32,
+                                (x + 1) as f32,
+                                (y + 1) as f32,
+                                (z + 1) as f32,
+                            )
+                            .with_epsilon(self.collision_epsilon),
+                        );
                     }
                 }
             }
@@ -154,23 +325,354 @@ impl Level {
     pub fn get_brightness(&self, x: i32, y: i32, z: i32) -> f32 {
         let dark = 0.8;
         let light = 1.0;
-        if x < 0 || y < 0 || z < 0 || x >= self.width || y >= self.depth || z >= self.height {
+        if y < 0 || y >= self.depth {
             return light;
         }
-        if y < self.light_depths[(x + z * self.width) as usize] {
+        let column = match self.column_at(x, z) {
+            Some(column) => column,
+            None => return light,
+        };
+        let xl = x.rem_euclid(COLUMN_SIZE);
+        let zl = z.rem_euclid(COLUMN_SIZE);
+        if y < column.light_depth(xl, zl) {
             return dark;
         }
+        // fluid lets some light through
+        if y < column.fluid_depth(xl, zl) {
+            return (dark + light) / 2.0;
+        }
         light
     }
 
+    /// Whether the tile at `x`/`y`/`z` gets full light, with nothing above it blocking any.
+    pub fn is_lit(&self, x: i32, y: i32, z: i32) -> bool {
+        self.get_brightness(x, y, z) == 1.0
+    }
+
+    /// The fluid any part of `aabb` is in.
+    pub fn fluid_in(&self, aabb: &AABB) -> Option<Fluid> {
+        let x0 = aabb.x0.floor() as i32;
+        let x1 = aabb.x1.floor() as i32;
+        let y0 = aabb.y0.floor() as i32;
+        let y1 = aabb.y1.floor() as i32;
+        let z0 = aabb.z0.floor() as i32;
+        let z1 = aabb.z1.floor() as i32;
+        for x in x0..=x1 {
+            for y in y0..=y1 {
+                for z in z0..=z1 {
+                    if let Some(fluid) = Fluid::of(self.get_tile(x, y, z)) {
+                        return Some(fluid);
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    /// Runs the tile updates that are due and the random tile ticks.
+    pub fn tick(&mut self) {
+        self.ticks += 1;
+        let mut due = vec![];
+        while let Some(&(tick, x, y, z)) = self.scheduled.first() {
+            if tick > self.ticks {
+                break;
+            }
+            self.scheduled.pop_first();
+            self.scheduled_tiles.remove(&(x, y, z));
+            due.push((x, y, z));
+        }
+        for (x, y, z) in due {
+            if let Some(tile) = tile::by_id(self.get_tile(x, y, z)) {
+                tile.tick(self, x, y, z);
+            }
+        }
+
+        let mut rng = rand::thread_rng();
+        for (cx, cz) in self.loaded_columns() {
+            for y0 in (0..self.depth).step_by(16) {
+                for _ in 0..RANDOM_TICKS {
+                    let x = cx * COLUMN_SIZE + rng.gen_range(0..COLUMN_SIZE);
+                    let y = y0 + rng.gen_range(0..16);
+                    let z = cz * COLUMN_SIZE + rng.gen_range(0..COLUMN_SIZE);
+                    if let Some(tile) = tile::by_id(self.get_tile(x, y, z)) {
+                        tile.random_tick(self, x, y, z);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Updates the tile at `x`/`y`/`z` in `delay` ticks, unless it already has an update coming.
+    pub fn schedule_update(&mut self, x: i32, y: i32, z: i32, delay: u64) {
+        if self.scheduled_tiles.insert((x, y, z)) {
+            self.scheduled.insert((self.ticks + delay, x, y, z));
+        }
+    }
+
+    /// Marks the tile with `id` that was removed from `x`/`y`/`z` as falling, for the game to
+    /// pick up with `take_falling` and drop.
+    pub fn start_falling(&mut self, x: i32, y: i32, z: i32, id: i32) {
+        self.falling.push((x, y, z, id));
+    }
+
+    pub fn take_falling(&mut self) -> Vec<(i32, i32, i32, i32)> {
+        std::mem::take(&mut self.falling)
+    }
+
+    pub fn add_entity(&mut self, entity: impl EntityTrait + 'static) -> EntityId {
+        let id = self.next_entity_id;
+        self.next_entity_id += 1;
+        self.entity_grid.insert(id, entity.entity().bb);
+        self.entities.insert(id, Rc::new(RefCell::new(entity)));
+        id
+    }
+
+    pub fn remove_entity(&mut self, id: EntityId) {
+        self.entities.remove(&id);
+        self.entity_grid.remove(id);
+    }
+
+    /// Tells the level that the entity with `id` moved to `bb`.
+    pub fn entity_moved(&mut self, id: EntityId, bb: AABB) {
+        if self.entities.contains_key(&id) {
+            self.entity_grid.insert(id, bb);
+        }
+    }
+
+    pub fn entity(&self, id: EntityId) -> Option<Rc<RefCell<dyn EntityTrait>>> {
+        self.entities.get(&id).cloned()
+    }
+
+    /// All entities in the order they were added. The level does not need to stay borrowed
+    /// while going through them, so they can tick, add and remove entities along the way.
+    pub fn entities(&self) -> Vec<(EntityId, Rc<RefCell<dyn EntityTrait>>)> {
+        self.entities
+            .iter()
+            .map(|(id, entity)| (*id, Rc::clone(entity)))
+            .collect()
+    }
+
+    /// The entities of type `T`, leaving out those that are borrowed mutably.
+    pub fn entities_of<T: 'static>(&self) -> Vec<(EntityId, Rc<RefCell<dyn EntityTrait>>)> {
+        self.entities()
+            .into_iter()
+            .filter(|(_, entity)| {
+                entity
+                    .try_borrow()
+                    .is_ok_and(|entity| entity.as_any().is::<T>())
+            })
+            .collect()
+    }
+
+    /// The entities whose bounding box overlapped `aabb` when they last moved.
+    pub fn entities_in(&self, aabb: &AABB) -> Vec<(EntityId, Rc<RefCell<dyn EntityTrait>>)> {
+        self.entity_grid
+            .query(aabb)
+            .into_iter()
+            .map(|id| (id, Rc::clone(&self.entities[&id])))
+            .collect()
+    }
+
+    /// The closest attackable entity a ray from `origin` along `dir` hits within `reach`, and
+    /// how far along the ray it is.
+    pub fn pick_entity(
+        &self,
+        origin: [f32; 3],
+        dir: [f32; 3],
+        reach: f32,
+    ) -> Option<(EntityId, f32)> {
+        let [x, y, z] = origin;
+        let [xd, yd, zd] = dir.map(|d| d * reach);
+        let along = AABB::new(x, y, z, x, y, z).expand(xd, yd, zd);
+        let mut closest = None;
+        for id in self.entity_grid.query(&along) {
+            let attackable = self.entities[&id]
+                .try_borrow()
+                .is_ok_and(|entity| entity.is_attackable());
+            let t = match self.entity_grid.bb(id).and_then(|bb| bb.clip(origin, dir)) {
+                Some(t) if attackable && t <= reach => t,
+                _ => continue,
+            };
+            if closest.is_none_or(|(_, closest_t)| t < closest_t) {
+                closest = Some((id, t));
+            }
+        }
+        closest
+    }
+
+    /// Lets a mob at `x`/`z` hurt the player for `damage`, for the game to pick up with
+    /// `take_player_hits`.
+    pub fn hurt_player(&mut self, damage: i32, x: f32, z: f32) {
+        self.player_hits.push((damage, x, z));
+    }
+
+    pub fn take_player_hits(&mut self) -> Vec<(i32, f32, f32)> {
+        std::mem::take(&mut self.player_hits)
+    }
+
+    /// Pushes every pushable entity away from the ones it overlaps.
+    pub fn push_entities_apart(&self) {
+        for (id, a) in self.entities() {
+            let bb = match self.entity_grid.bb(id) {
+                Some(bb) => *bb,
+                None => continue,
+            };
+            let mut a = a.borrow_mut();
+            if !a.is_pushable() {
+                continue;
+            }
+            // each pair once
+            for other in self.entity_grid.query(&bb) {
+                if other <= id {
+                    continue;
+                }
+                let mut b = self.entities[&other].borrow_mut();
+                if b.is_pushable() {
+                    a.entity_mut().push(b.entity_mut());
+                }
+            }
+        }
+    }
+
+    /// Schedules updates for the tiles at and around `x`/`y`/`z` that react to changes.
+    fn update_neighbors(&mut self, x: i32, y: i32, z: i32) {
+        let neighbors = [
+            (0, 0, 0),
+            (-1, 0, 0),
+            (1, 0, 0),
+            (0, -1, 0),
+            (0, 1, 0),
+            (0, 0, -1),
+            (0, 0, 1),
+        ];
+        for (xd, yd, zd) in neighbors {
+            let (x, y, z) = (x + xd, y + yd, z + zd);
+            if let Some(delay) = tile::by_id(self.get_tile(x, y, z)).and_then(|t| t.update_delay())
+            {
+                self.schedule_update(x, y, z, delay);
+            }
+        }
+    }
+
     pub fn set_tile(&mut self, x: i32, y: i32, z: i32, tile_type: i32) {
-        if x < 0 || y < 0 || z < 0 || x >= self.width || y >= self.depth || z >= self.height {
+        self.set_tile_with_data(x, y, z, tile_type, 0);
+    }
+
+    pub fn set_tile_with_data(&mut self, x: i32, y: i32, z: i32, tile_type: i32, data: i32) {
+        if y < 0 || y >= self.depth {
             return;
         }
-        self.blocks[((y * self.height + z) * self.width + x) as usize] = tile_type as u8;
+        let xl = x.rem_euclid(COLUMN_SIZE);
+        let zl = z.rem_euclid(COLUMN_SIZE);
+        match self.column_at_mut(x, z) {
+            Some(column) => {
+                column.set_tile(xl, y, zl, tile_type as u8);
+                column.set_data(xl, y, zl, data as u8);
+            }
+            None => return,
+        }
         self.calc_light_depths(x, z, 1, 1);
         for level_listener in &self.level_listeners {
             level_listener.borrow_mut().tile_changed(x, y, z);
         }
+        self.update_neighbors(x, y, z);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, process};
+
+    use super::*;
+    use crate::character::zombie::Zombie;
+    use crate::falling_tile::FallingTile;
+    use crate::player::Player;
+
+    #[test]
+    fn keeps_entities_by_id_and_finds_them_by_type_and_place() {
+        let level = Rc::new(RefCell::new(Level::with_dir(64, None)));
+        let mut zombie = Zombie::new(Rc::clone(&level), 0.0, 0.0, 0.0);
+        zombie.set_pos(10.5, 50.0, 10.5);
+        let zombie = level.borrow_mut().add_entity(zombie);
+        let falling_tile = FallingTile::new(Rc::clone(&level), 20, 50, 20, tile::SAND.id);
+        let falling_tile = level.borrow_mut().add_entity(falling_tile);
+        assert_ne!(zombie, falling_tile);
+
+        let level_ref = level.borrow();
+        let zombies = level_ref.entities_of::<Zombie>();
+        assert_eq!(zombies.len(), 1);
+        assert_eq!(zombies[0].0, zombie);
+        let near = AABB::new(20.0, 50.0, 20.0, 21.0, 51.0, 21.0);
+        let found: Vec<EntityId> = level_ref.entities_in(&near).iter().map(|e| e.0).collect();
+        assert_eq!(found, [falling_tile]);
+        drop(level_ref);
+
+        level.borrow_mut().remove_entity(zombie);
+        assert!(level.borrow().entity(zombie).is_none());
+        let mut zombie = Zombie::new(Rc::clone(&level), 0.0, 0.0, 0.0);
+        zombie.set_pos(0.0, 50.0, 0.0);
+        // ids are not reused
+        let id = level.borrow_mut().add_entity(zombie);
+        assert!(id > falling_tile);
+    }
+
+    #[test]
+    fn saves_and_drops_regions_left_behind() {
+        let dir = env::temp_dir().join(format!("rd-132328-regions-{}", process::id()));
+        let mut level = Level::with_dir(64, Some(dir.clone()));
+        level.update_columns(0.0, 0.0);
+        let y = level.depth * 2 / 3 + 1;
+        level.set_tile(5, y, 5, tile::ROCK.id);
+        assert!(level.regions.contains_key(&(0, 0)));
+
+        let far = (REGION_SIZE * COLUMN_SIZE * 4) as f32;
+        level.update_columns(far, far);
+        assert!(!level.regions.contains_key(&(0, 0)));
+        assert!(level.regions.len() <= 4);
+
+        level.update_columns(0.0, 0.0);
+        let tile = level.get_tile(5, y, 5);
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(tile, tile::ROCK.id);
+    }
+
+    #[test]
+    fn picks_up_falling_tiles_left_behind() {
+        let dir = env::temp_dir().join(format!("rd-132328-falling-{}", process::id()));
+        let mut level = Level::with_dir(64, Some(dir.clone()));
+        level.update_columns(0.0, 0.0);
+        let y = level.depth * 2 / 3 + 5;
+        level.set_tile(5, y, 5, tile::SAND.id);
+
+        let far = (REGION_SIZE * COLUMN_SIZE * 4) as f32;
+        level.update_columns(far, far);
+        assert!(level.scheduled_tiles.is_empty());
+        level.update_columns(0.0, 0.0);
+        for _ in 0..5 {
+            level.tick();
+        }
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(level.take_falling(), [(5, y, 5, tile::SAND.id)]);
+    }
+
+    #[test]
+    fn pushes_overlapping_zombies_apart() {
+        let level = Rc::new(RefCell::new(Level::with_dir(64, None)));
+        let mut ids = vec![];
+        for x in [10.0, 10.2] {
+            let mut zombie = Zombie::new(Rc::clone(&level), 0.0, 0.0, 0.0);
+            zombie.set_pos(x, 50.0, 10.0);
+            zombie.entity.xd = 0.0;
+            ids.push(level.borrow_mut().add_entity(zombie));
+        }
+        let mut player = Player::new(Rc::clone(&level));
+        player.set_pos(30.0, 50.0, 30.0);
+        let player = level.borrow_mut().add_entity(player);
+        level.borrow().push_entities_apart();
+
+        let xd = |id| level.borrow().entity(id).unwrap().borrow().entity().xd;
+        assert!(xd(ids[0]) < 0.0);
+        assert!(xd(ids[1]) > 0.0);
+        assert_eq!(xd(player), 0.0);
     }
 }
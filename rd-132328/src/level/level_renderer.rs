@@ -1,3 +1,4 @@
+use std::collections::HashMap;
 use std::rc::Rc;
 use std::sync::atomic::Ordering;
 use std::time::UNIX_EPOCH;
@@ -5,7 +6,7 @@ use std::{cell::RefCell, time::SystemTime};
 
 use crate::glu::*;
 
-use crate::{hit_result::HitResult, player::Player};
+use crate::{entity::Entity, hit_result::HitResult};
 
 use super::{
     chunk::{self, Chunk},
@@ -20,53 +21,22 @@ const CHUNK_SIZE: i32 = 16;
 
 pub struct LevelRenderer {
     level: Rc<RefCell<Level>>,
-    chunks: Vec<Option<Chunk>>,
-    x_chunks: i32,
+    chunks: HashMap<(i32, i32, i32), Chunk>,
+    depth: i32,
     y_chunks: i32,
-    z_chunks: i32,
     t: Tesselator,
 }
 
 impl LevelRenderer {
     pub fn new(level: Rc<RefCell<Level>>) -> Rc<RefCell<LevelRenderer>> {
-        let x_chunks = level.borrow().width / CHUNK_SIZE;
-        let y_chunks = level.borrow().depth / CHUNK_SIZE;
-        let z_chunks = level.borrow().height / CHUNK_SIZE;
-
-        let mut chunks: Vec<Option<Chunk>> = std::iter::repeat_with(|| None)
-            .take((x_chunks * y_chunks * z_chunks) as usize)
-            .collect();
-
-        for x in 0..x_chunks {
-            for y in 0..y_chunks {
-                for z in 0..z_chunks {
-                    let x0 = x * CHUNK_SIZE;
-                    let y0 = y * CHUNK_SIZE;
-                    let z0 = z * CHUNK_SIZE;
-                    let mut x1 = (x + 1) * CHUNK_SIZE;
-                    let mut y1 = (y + 1) * CHUNK_SIZE;
-                    let mut z1 = (z + 1) * CHUNK_SIZE;
-                    if x1 > level.borrow().width {
-                        x1 = level.borrow().width;
-                    }
-                    if y1 > level.borrow().depth {
-                        y1 = level.borrow().depth;
-                    }
-                    if z1 > level.borrow().height {
-                        z1 = level.borrow().height;
-                    }
-                    chunks[((x + y * x_chunks) * z_chunks + z) as usize] =
-                        Some(Chunk::new(Rc::clone(&level), x0, y0, z0, x1, y1, z1));
-                }
-            }
-        }
+        let depth = level.borrow().depth;
+        let y_chunks = depth / CHUNK_SIZE;
 
         let lr = Rc::new(RefCell::new(LevelRenderer {
             level,
-            chunks,
-            x_chunks,
+            chunks: HashMap::new(),
+            depth,
             y_chunks,
-            z_chunks,
             t: Tesselator::new(),
         }));
 
@@ -75,25 +45,36 @@ impl LevelRenderer {
             .borrow_mut()
             .add_listener(Rc::clone(&lr) as Rc<RefCell<dyn LevelListener>>);
 
+        let columns = lr.borrow().level.borrow().loaded_columns();
+        for (x, z) in columns {
+            lr.borrow_mut().column_loaded(x, z);
+        }
+
         lr
     }
 
-    pub fn render(&mut self, _player: &Player, layer: i32) {
+    pub fn render(&mut self, frustum: &Frustum, layer: i32) {
         chunk::REBUILT_THIS_FRAME.store(0, Ordering::SeqCst);
-        let frustum = Frustum::get_frustum();
 
-        for chunk in &mut self.chunks {
-            if let Some(chunk) = chunk {
-                if frustum.lock().unwrap().cube_in_frustum_aabb(&chunk.aabb) {
-                    chunk.render(layer);
-                }
+        for chunk in self.chunks.values_mut() {
+            if frustum.cube_in_frustum_aabb(&chunk.aabb) {
+                chunk.render(layer);
+            }
+        }
+    }
+
+    /// Rebuilds every chunk that needs it instead of a couple per frame.
+    pub fn rebuild_all(&mut self) {
+        for chunk in self.chunks.values_mut() {
+            if chunk.is_dirty() {
+                chunk.rebuild_now();
             }
         }
     }
 
-    pub fn pick(&mut self, player: &Player) {
+    pub fn pick(&mut self, player: &Entity) {
         let r = 3.0;
-        let box_aabb = player.entity.bb.grow(r, r, r);
+        let box_aabb = player.bb.grow(r, r, r);
         let x0 = box_aabb.x0 as i32;
         let x1 = (box_aabb.x1 + 1.0) as i32;
         let y0 = box_aabb.y0 as i32;
@@ -109,18 +90,14 @@ impl LevelRenderer {
                     glPushName(y as u32);
                     for z in z0..z1 {
                         glPushName(z as u32);
-                        if self.level.borrow().is_solid_tile(x, y, z) {
+                        // everything but fluids can be picked
+                        let id = self.level.borrow().get_tile(x, y, z);
+                        if tile::by_id(id).is_some_and(|tile| tile.fluid().is_none()) {
                             glPushName(0);
                             for i in 0..6 {
                                 glPushName(i);
                                 self.t.init();
-                                tile::ROCK.lock().unwrap().render_face(
-                                    &mut self.t,
-                                    x,
-                                    y,
-                                    z,
-                                    i as i32,
-                                );
+                                tile::ROCK.render_face(&mut self.t, x, y, z, i as i32);
                                 self.t.flush();
                                 glPopName();
                             }
@@ -152,47 +129,30 @@ impl LevelRenderer {
                 ((current_time_millis as f64 / 100.0).sin() * 0.2 + 0.4) as f32,
             );
             self.t.init();
-            tile::ROCK
-                .lock()
-                .unwrap()
-                .render_face(&mut self.t, h.x, h.y, h.z, h.f);
+            tile::ROCK.render_face(&mut self.t, h.x, h.y, h.z, h.f);
             self.t.flush();
             glDisable(GL_BLEND);
         }
     }
 
     pub fn set_dirty(&mut self, x0: i32, y0: i32, z0: i32, x1: i32, y1: i32, z1: i32) {
-        let mut x0 = x0 / CHUNK_SIZE;
-        let mut x1 = x1 / CHUNK_SIZE;
+        let x0 = x0.div_euclid(CHUNK_SIZE);
+        let x1 = x1.div_euclid(CHUNK_SIZE);
         let mut y0 = y0 / CHUNK_SIZE;
         let mut y1 = y1 / CHUNK_SIZE;
-        let mut z0 = z0 / CHUNK_SIZE;
-        let mut z1 = z1 / CHUNK_SIZE;
-        if x0 < 0 {
-            x0 = 0;
-        }
+        let z0 = z0.div_euclid(CHUNK_SIZE);
+        let z1 = z1.div_euclid(CHUNK_SIZE);
         if y0 < 0 {
             y0 = 0;
         }
-        if z0 < 0 {
-            z0 = 0;
-        }
-        if x1 >= self.x_chunks {
-            x1 = self.x_chunks - 1;
-        }
         if y1 >= self.y_chunks {
             y1 = self.y_chunks - 1;
         }
-        if z1 >= self.z_chunks {
-            z1 = self.z_chunks - 1;
-        }
 
         for x in x0..=x1 {
             for y in y0..=y1 {
                 for z in z0..=z1 {
-                    if let Some(chunk) =
-                        &mut self.chunks[((x + y * self.x_chunks) * self.z_chunks + z) as usize]
-                    {
+                    if let Some(chunk) = self.chunks.get_mut(&(x, y, z)) {
                         chunk.set_dirty();
                     }
                 }
@@ -210,10 +170,50 @@ impl LevelListener for LevelRenderer {
         self.set_dirty(x - 1, y0 - 1, z - 1, x + 1, y1 + 1, z + 1);
     }
 
-    fn all_changed(&mut self) {
-        let x1 = self.level.borrow().width;
-        let y1 = self.level.borrow().depth;
-        let z1 = self.level.borrow().height;
-        self.set_dirty(0, 0, 0, x1, y1, z1);
+    fn column_loaded(&mut self, x: i32, z: i32) {
+        for y in 0..self.y_chunks {
+            let x0 = x * CHUNK_SIZE;
+            let y0 = y * CHUNK_SIZE;
+            let z0 = z * CHUNK_SIZE;
+            let mut y1 = (y + 1) * CHUNK_SIZE;
+            if y1 > self.depth {
+                y1 = self.depth;
+            }
+            self.chunks.insert(
+                (x, y, z),
+                Chunk::new(
+                    Rc::clone(&self.level),
+                    x0,
+                    y0,
+                    z0,
+                    x0 + CHUNK_SIZE,
+                    y1,
+                    z0 + CHUNK_SIZE,
+                ),
+            );
+        }
+        // faces along the borders of the neighbouring columns may have become hidden
+        self.set_dirty(
+            x * CHUNK_SIZE - 1,
+            0,
+            z * CHUNK_SIZE - 1,
+            (x + 1) * CHUNK_SIZE,
+            self.depth,
+            (z + 1) * CHUNK_SIZE,
+        );
+    }
+
+    fn column_unloaded(&mut self, x: i32, z: i32) {
+        for y in 0..self.y_chunks {
+            self.chunks.remove(&(x, y, z));
+        }
+        self.set_dirty(
+            x * CHUNK_SIZE - 1,
+            0,
+            z * CHUNK_SIZE - 1,
+            (x + 1) * CHUNK_SIZE,
+            self.depth,
+            (z + 1) * CHUNK_SIZE,
+        );
     }
 }
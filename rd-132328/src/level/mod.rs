@@ -1,7 +1,12 @@
 pub mod chunk;
-mod frustrum;
+mod column;
+mod entity_grid;
+pub mod fluid;
+pub mod frustrum;
 pub mod level;
-mod level_listener;
+pub mod level_listener;
 pub mod level_renderer;
-mod tesselator;
-mod tile;
+mod region;
+pub mod tesselator;
+pub mod tile;
+mod tree;
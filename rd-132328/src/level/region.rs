@@ -0,0 +1,77 @@
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::prelude::*;
+use std::path::{Path, PathBuf};
+
+use flate2::bufread::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+
+/// Number of columns along each side of a region file.
+pub const REGION_SIZE: i32 = 32;
+
+/// A gzipped file holding the blocks of up to `REGION_SIZE * REGION_SIZE` columns.
+pub struct Region {
+    path: PathBuf,
+    columns: HashMap<(i32, i32), Vec<u8>>,
+    dirty: bool,
+}
+
+impl Region {
+    pub fn open(dir: &Path, rx: i32, rz: i32) -> Region {
+        let path = dir.join("region").join(format!("r.{}.{}.dat", rx, rz));
+        let mut columns = HashMap::new();
+        if let Ok(file) = fs::read(&path) {
+            let mut gz = GzDecoder::new(&file[..]);
+            let count = read_i32(&mut gz);
+            for _ in 0..count {
+                let x = read_i32(&mut gz);
+                let z = read_i32(&mut gz);
+                let len = read_i32(&mut gz);
+                let mut blocks = vec![0u8; len as usize];
+                gz.read_exact(&mut blocks).unwrap();
+                columns.insert((x, z), blocks);
+            }
+        }
+
+        Region {
+            path,
+            columns,
+            dirty: false,
+        }
+    }
+
+    pub fn get(&self, x: i32, z: i32) -> Option<&Vec<u8>> {
+        self.columns.get(&(x, z))
+    }
+
+    pub fn put(&mut self, x: i32, z: i32, blocks: &[u8]) {
+        self.columns.insert((x, z), blocks.to_vec());
+        self.dirty = true;
+    }
+
+    pub fn save(&mut self) {
+        if !self.dirty {
+            return;
+        }
+        fs::create_dir_all(self.path.parent().unwrap()).unwrap();
+        let w = File::create(&self.path).unwrap();
+        let mut e = GzEncoder::new(w, Compression::default());
+        e.write_all(&(self.columns.len() as i32).to_be_bytes())
+            .unwrap();
+        for ((x, z), blocks) in &self.columns {
+            e.write_all(&x.to_be_bytes()).unwrap();
+            e.write_all(&z.to_be_bytes()).unwrap();
+            e.write_all(&(blocks.len() as i32).to_be_bytes()).unwrap();
+            e.write_all(blocks).unwrap();
+        }
+        e.finish().unwrap();
+        self.dirty = false;
+    }
+}
+
+fn read_i32(r: &mut impl Read) -> i32 {
+    let mut buf = [0u8; 4];
+    r.read_exact(&mut buf).unwrap();
+    i32::from_be_bytes(buf)
+}
@@ -4,6 +4,12 @@ use crate::glu::*;
 
 const MAX_VERTICES: usize = 100000;
 
+/// A zeroed buffer allocated on the heap directly. `Box::new` would build it on the stack first
+/// in debug builds, which is more than a test thread's stack holds.
+fn zeroed<const N: usize>() -> Box<[GLfloat; N]> {
+    vec![0.0; N].into_boxed_slice().try_into().unwrap()
+}
+
 pub struct Tesselator {
     vertex_buffer: Box<[GLfloat; MAX_VERTICES * 3]>,
     tex_coord_buffer: Box<[GLfloat; MAX_VERTICES * 2]>,
@@ -21,9 +27,9 @@ pub struct Tesselator {
 impl Tesselator {
     pub fn new() -> Tesselator {
         Tesselator {
-            vertex_buffer: Box::new([0.0; MAX_VERTICES * 3]),
-            tex_coord_buffer: Box::new([0.0; MAX_VERTICES * 2]),
-            color_buffer: Box::new([0.0; MAX_VERTICES * 3]),
+            vertex_buffer: zeroed(),
+            tex_coord_buffer: zeroed(),
+            color_buffer: zeroed(),
             vertices: 0,
             u: 0.0,
             v: 0.0,
@@ -1,44 +1,254 @@
-use std::sync::Mutex;
+use rand::Rng;
 
-use super::{level::Level, tesselator::Tesselator};
+use super::{fluid, fluid::Fluid, level::Level, tesselator::Tesselator, tree};
 
-pub static ROCK: Mutex<Tile> = Mutex::new(Tile::new(0));
-pub static GRASS: Mutex<Tile> = Mutex::new(Tile::new(1));
+pub static ROCK: Tile = Tile::new(1, 1);
+pub static GRASS: Tile = Tile::new(2, 0);
+pub static DIRT: Tile = Tile::new(3, 2);
+pub static COBBLESTONE: Tile = Tile::new(4, 16);
+pub static PLANKS: Tile = Tile::new(5, 4);
+pub static WATER: Tile = Tile::with_shape(6, 14, Shape::Fluid(Fluid::Water));
+pub static LAVA: Tile = Tile::with_shape(7, 30, Shape::Fluid(Fluid::Lava));
+pub static SAPLING: Tile = Tile::with_shape(8, 15, Shape::Cross);
+pub static LOG: Tile = Tile::new(9, 20).with_top_tex(21);
+pub static LEAVES: Tile = Tile::new(10, 22);
+pub static SAND: Tile = Tile::new(11, 18).falling();
+pub static GRAVEL: Tile = Tile::new(12, 19).falling();
+
+/// Chunk layer that fluids and plants are rendered in, after the lit (0) and shadowed (1) ones.
+pub const TRANSLUCENT_LAYER: i32 = 2;
+/// Ticks from losing its support until a falling tile drops.
+const FALL_DELAY: u64 = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Shape {
+    Cube,
+    Cross, // two crossed quads, for plants
+    Fluid(Fluid),
+}
 
 pub struct Tile {
+    pub id: i32,
     tex: i32,
+    top_tex: i32, // on the top and bottom faces
+    pub shape: Shape,
+    falls: bool, // drops when there is nothing below it
+}
+
+/// Looks up the tile stored under `id` in the level.
+pub fn by_id(id: i32) -> Option<&'static Tile> {
+    match id {
+        1 => Some(&ROCK),
+        2 => Some(&GRASS),
+        3 => Some(&DIRT),
+        4 => Some(&COBBLESTONE),
+        5 => Some(&PLANKS),
+        6 => Some(&WATER),
+        7 => Some(&LAVA),
+        8 => Some(&SAPLING),
+        9 => Some(&LOG),
+        10 => Some(&LEAVES),
+        11 => Some(&SAND),
+        12 => Some(&GRAVEL),
+        _ => None,
+    }
 }
 
 impl Tile {
-    pub const fn new(tex: i32) -> Tile {
-        Tile { tex }
-    }
-
-    pub fn render(
-        &mut self,
-        t: &mut Tesselator,
-        level: &Level,
-        layer: i32,
-        x: i32,
-        y: i32,
-        z: i32,
-    ) {
-        let u0 = self.tex as f32 / 16.0;
-        let u1 = u0 + (1.0 / 16.0);
-        let v0 = 0.0;
-        let v1 = v0 + (1.0 / 16.0);
+    pub const fn new(id: i32, tex: i32) -> Tile {
+        Tile::with_shape(id, tex, Shape::Cube)
+    }
+
+    const fn with_shape(id: i32, tex: i32, shape: Shape) -> Tile {
+        Tile {
+            id,
+            tex,
+            top_tex: tex,
+            shape,
+            falls: false,
+        }
+    }
+
+    const fn with_top_tex(mut self, top_tex: i32) -> Tile {
+        self.top_tex = top_tex;
+        self
+    }
+
+    const fn falling(mut self) -> Tile {
+        self.falls = true;
+        self
+    }
+
+    pub fn fluid(&self) -> Option<Fluid> {
+        match self.shape {
+            Shape::Fluid(fluid) => Some(fluid),
+            _ => None,
+        }
+    }
+
+    /// Whether entities collide with the tile and it blocks light.
+    pub fn is_solid(&self) -> bool {
+        self.shape == Shape::Cube
+    }
+
+    /// Ticks from a change next to the tile until it is updated, `None` if it does not care.
+    pub fn update_delay(&self) -> Option<u64> {
+        if self.falls {
+            return Some(FALL_DELAY);
+        }
+        self.fluid().map(|fluid| fluid.tick_delay())
+    }
+
+    /// Called when an update scheduled for the tile is due.
+    pub fn tick(&self, level: &mut Level, x: i32, y: i32, z: i32) {
+        if self.fluid().is_some() {
+            fluid::update(level, x, y, z);
+        }
+        if self.falls && y > 0 && !level.is_solid_tile(x, y - 1, z) {
+            level.set_tile(x, y, z, 0);
+            level.start_falling(x, y, z, self.id);
+        }
+    }
+
+    /// Called for a few tiles picked at random from every chunk each tick.
+    pub fn random_tick(&self, level: &mut Level, x: i32, y: i32, z: i32) {
+        if self.id == SAPLING.id && level.is_lit(x, y, z) && rand::random::<f32>() < 0.25 {
+            tree::grow(level, x, y, z);
+        }
+        if self.id == GRASS.id {
+            grow_grass(level, x, y, z);
+        }
+    }
+
+    pub fn render(&self, t: &mut Tesselator, level: &Level, layer: i32, x: i32, y: i32, z: i32) {
+        match self.shape {
+            Shape::Cube if layer != TRANSLUCENT_LAYER => self.render_cube(t, level, layer, x, y, z),
+            Shape::Cross if layer == TRANSLUCENT_LAYER => self.render_cross(t, level, x, y, z),
+            Shape::Fluid(_) if layer == TRANSLUCENT_LAYER => self.render_fluid(t, level, x, y, z),
+            _ => {}
+        }
+    }
+
+    fn render_cube(&self, t: &mut Tesselator, level: &Level, layer: i32, x: i32, y: i32, z: i32) {
         let c1 = 1.0;
         let c2 = 0.8;
         let c3 = 0.6;
-        let x0 = x as f32;
-        let x1 = x as f32 + 1.0;
-        let y0 = y as f32;
-        let y1 = y as f32 + 1.0;
-        let z0 = z as f32;
-        let z1 = z as f32 + 1.0;
-        let br = level.get_brightness(x, y - 1, z) * c1;
-        if !level.is_solid_tile(x, y - 1, z) && ((br == c1) ^ (layer == 1)) {
+        for face in 0..6 {
+            let (xn, yn, zn, c) = match face {
+                0 => (x, y - 1, z, c1),
+                1 => (x, y + 1, z, c1),
+                2 => (x, y, z - 1, c2),
+                3 => (x, y, z + 1, c2),
+                4 => (x - 1, y, z, c3),
+                _ => (x + 1, y, z, c3),
+            };
+            let br = level.get_brightness(xn, yn, zn) * c;
+            if !level.is_solid_tile(xn, yn, zn) && ((br == c) ^ (layer == 1)) {
+                t.color(br, br, br);
+                self.render_tex_face(t, x as f32, y as f32, z as f32, face, 1.0);
+            }
+        }
+    }
+
+    /// Renders the faces that do not touch the same fluid, with the top lowered to its level.
+    fn render_fluid(&self, t: &mut Tesselator, level: &Level, x: i32, y: i32, z: i32) {
+        let height = if level.get_tile(x, y + 1, z) == self.id {
+            1.0
+        } else {
+            Fluid::height(level.get_data(x, y, z))
+        };
+        for face in 0..6 {
+            let (xn, yn, zn, c) = match face {
+                0 => (x, y - 1, z, 1.0),
+                1 => (x, y + 1, z, 1.0),
+                2 => (x, y, z - 1, 0.8),
+                3 => (x, y, z + 1, 0.8),
+                4 => (x - 1, y, z, 0.6),
+                _ => (x + 1, y, z, 0.6),
+            };
+            let neighbor = level.get_tile(xn, yn, zn);
+            let hidden = neighbor == self.id || level.is_solid_tile(xn, yn, zn);
+            // the lowered surface is visible even when something sits on top
+            if hidden && !(face == 1 && height < 1.0) {
+                continue;
+            }
+            let br = level.get_brightness(xn, yn, zn) * c;
             t.color(br, br, br);
+            self.render_tex_face(t, x as f32, y as f32, z as f32, face, height);
+        }
+    }
+
+    /// Renders two quads crossing diagonally through the tile, seen from both sides.
+    fn render_cross(&self, t: &mut Tesselator, level: &Level, x: i32, y: i32, z: i32) {
+        let br = level.get_brightness(x, y, z);
+        t.color(br, br, br);
+        self.render_cross_quads(t, x as f32, y as f32, z as f32);
+    }
+
+    fn render_cross_quads(&self, t: &mut Tesselator, x: f32, y: f32, z: f32) {
+        let u0 = (self.tex % 16) as f32 / 16.0;
+        let u1 = u0 + (1.0 / 16.0);
+        let v0 = (self.tex / 16) as f32 / 16.0;
+        let v1 = v0 + (1.0 / 16.0);
+        for (xa, za, xb, zb) in [(0.0, 0.0, 1.0, 1.0), (0.0, 1.0, 1.0, 0.0)] {
+            let (x0, z0, x1, z1) = (x + xa, z + za, x + xb, z + zb);
+            t.tex(u0, v1);
+            t.vertex(x0, y, z0);
+            t.tex(u1, v1);
+            t.vertex(x1, y, z1);
+            t.tex(u1, v0);
+            t.vertex(x1, y + 1.0, z1);
+            t.tex(u0, v0);
+            t.vertex(x0, y + 1.0, z0);
+
+            t.tex(u0, v0);
+            t.vertex(x0, y + 1.0, z0);
+            t.tex(u1, v0);
+            t.vertex(x1, y + 1.0, z1);
+            t.tex(u1, v1);
+            t.vertex(x1, y, z1);
+            t.tex(u0, v1);
+            t.vertex(x0, y, z0);
+        }
+    }
+
+    /// Renders the tile at the origin for the GUI, cubes shaded as if lit from above.
+    pub fn render_gui(&self, t: &mut Tesselator) {
+        self.render_shaded(t, 1.0);
+    }
+
+    /// Renders the tile at the origin like `render_gui`, darkened to `br`.
+    pub fn render_shaded(&self, t: &mut Tesselator, br: f32) {
+        if self.shape == Shape::Cross {
+            t.color(br, br, br);
+            self.render_cross_quads(t, 0.0, 0.0, 0.0);
+            return;
+        }
+        for face in 0..6 {
+            let c = match face {
+                0 => 0.5,
+                1 => 1.0,
+                2 | 3 => 0.8,
+                _ => 0.6,
+            } * br;
+            t.color(c, c, c);
+            self.render_tex_face(t, 0.0, 0.0, 0.0, face, 1.0);
+        }
+    }
+
+    fn render_tex_face(&self, t: &mut Tesselator, x: f32, y: f32, z: f32, face: i32, height: f32) {
+        let tex = if face < 2 { self.top_tex } else { self.tex };
+        let u0 = (tex % 16) as f32 / 16.0;
+        let u1 = u0 + (1.0 / 16.0);
+        let v0 = (tex / 16) as f32 / 16.0;
+        let v1 = v0 + (1.0 / 16.0);
+        let x0 = x;
+        let x1 = x + 1.0;
+        let y0 = y;
+        let y1 = y + height;
+        let z0 = z;
+        let z1 = z + 1.0;
+        if face == 0 {
             t.tex(u0, v1);
             t.vertex(x0, y0, z1);
             t.tex(u0, v0);
@@ -48,10 +258,7 @@ impl Tile {
             t.tex(u1, v1);
             t.vertex(x1, y0, z1);
         }
-
-        let br = level.get_brightness(x, y + 1, z) * c1;
-        if !level.is_solid_tile(x, y + 1, z) && ((br == c1) ^ (layer == 1)) {
-            t.color(br, br, br);
+        if face == 1 {
             t.tex(u1, v1);
             t.vertex(x1, y1, z1);
             t.tex(u1, v0);
@@ -61,10 +268,7 @@ impl Tile {
             t.tex(u0, v1);
             t.vertex(x0, y1, z1);
         }
-
-        let br = level.get_brightness(x, y, z - 1) * c2;
-        if !level.is_solid_tile(x, y, z - 1) && ((br == c2) ^ (layer == 1)) {
-            t.color(br, br, br);
+        if face == 2 {
             t.tex(u1, v0);
             t.vertex(x0, y1, z0);
             t.tex(u0, v0);
@@ -74,10 +278,7 @@ impl Tile {
             t.tex(u1, v1);
             t.vertex(x0, y0, z0);
         }
-
-        let br = level.get_brightness(x, y, z + 1) * c2;
-        if !level.is_solid_tile(x, y, z + 1) && ((br == c2) ^ (layer == 1)) {
-            t.color(br, br, br);
+        if face == 3 {
             t.tex(u0, v0);
             t.vertex(x0, y1, z1);
             t.tex(u0, v1);
@@ -87,10 +288,7 @@ impl Tile {
             t.tex(u1, v0);
             t.vertex(x1, y1, z1);
         }
-
-        let br = level.get_brightness(x - 1, y, z) * c3;
-        if !level.is_solid_tile(x - 1, y, z) && ((br == c3) ^ (layer == 1)) {
-            t.color(br, br, br);
+        if face == 4 {
             t.tex(u1, v0);
             t.vertex(x0, y1, z1);
             t.tex(u0, v0);
@@ -100,10 +298,7 @@ impl Tile {
             t.tex(u1, v1);
             t.vertex(x0, y0, z1);
         }
-
-        let br = level.get_brightness(x + 1, y, z) * c3;
-        if !level.is_solid_tile(x + 1, y, z) && ((br == c3) ^ (layer == 1)) {
-            t.color(br, br, br);
+        if face == 5 {
             t.tex(u0, v1);
             t.vertex(x1, y0, z1);
             t.tex(u1, v1);
@@ -160,3 +355,54 @@ impl Tile {
         }
     }
 }
+
+/// Covered grass turns to dirt, grass in the light spreads to a random lit dirt tile next to it.
+fn grow_grass(level: &mut Level, x: i32, y: i32, z: i32) {
+    if !level.is_lit(x, y, z) {
+        level.set_tile(x, y, z, DIRT.id);
+        return;
+    }
+    let mut rng = rand::thread_rng();
+    let xt = x + rng.gen_range(-1..=1);
+    let yt = y + rng.gen_range(-3..=1);
+    let zt = z + rng.gen_range(-1..=1);
+    if level.get_tile(xt, yt, zt) == DIRT.id && level.is_lit(xt, yt, zt) {
+        level.set_tile(xt, yt, zt, GRASS.id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn level() -> (Level, i32) {
+        let mut level = Level::with_dir(64, None);
+        level.update_columns(0.0, 0.0);
+        let surface = level.depth * 2 / 3;
+        (level, surface)
+    }
+
+    #[test]
+    fn covered_grass_turns_to_dirt() {
+        let (mut level, y) = level();
+        level.set_tile(8, y + 1, 8, ROCK.id);
+        GRASS.random_tick(&mut level, 8, y, 8);
+        assert_eq!(level.get_tile(8, y, 8), DIRT.id);
+        GRASS.random_tick(&mut level, 9, y, 8);
+        assert_eq!(level.get_tile(9, y, 8), GRASS.id);
+    }
+
+    #[test]
+    fn grass_spreads_to_lit_dirt_only() {
+        let (mut level, y) = level();
+        level.set_tile(8, y, 8, DIRT.id);
+        level.set_tile(8, y, 10, DIRT.id);
+        level.set_tile(8, y + 1, 10, ROCK.id);
+        for _ in 0..1000 {
+            GRASS.random_tick(&mut level, 9, y, 8);
+            GRASS.random_tick(&mut level, 9, y, 10);
+        }
+        assert_eq!(level.get_tile(8, y, 8), GRASS.id);
+        assert_eq!(level.get_tile(8, y, 10), DIRT.id);
+    }
+}
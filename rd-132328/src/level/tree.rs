@@ -0,0 +1,65 @@
+use super::level::Level;
+use super::tile;
+
+/// Grows the sapling at `x`/`y`/`z` into a tree, if there is room for one.
+pub fn grow(level: &mut Level, x: i32, y: i32, z: i32) {
+    let height = 4 + (rand::random::<f32>() * 3.0) as i32;
+    let top = y + height;
+    if top + 1 >= level.depth {
+        return;
+    }
+    for yy in (y + 1)..=top {
+        if level.get_tile(x, yy, z) != 0 {
+            return;
+        }
+    }
+
+    // two wide layers below the top of the trunk, two narrow ones from the top up
+    for yy in (top - 2)..=(top + 1) {
+        let r = if yy < top { 2 } else { 1 };
+        for xx in (x - r)..=(x + r) {
+            for zz in (z - r)..=(z + r) {
+                let corner = (xx - x).abs() == r && (zz - z).abs() == r;
+                if corner && (yy > top || rand::random::<bool>()) {
+                    continue;
+                }
+                if level.get_tile(xx, yy, zz) == 0 {
+                    level.set_tile(xx, yy, zz, tile::LEAVES.id);
+                }
+            }
+        }
+    }
+    for yy in y..top {
+        level.set_tile(x, yy, z, tile::LOG.id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn grows_a_trunk_with_leaves() {
+        let mut level = Level::with_dir(64, None);
+        level.update_columns(0.0, 0.0);
+        let y = level.depth * 2 / 3 + 1;
+        level.set_tile(0, y, 0, tile::SAPLING.id);
+        grow(&mut level, 0, y, 0);
+        for yy in y..y + 4 {
+            assert_eq!(level.get_tile(0, yy, 0), tile::LOG.id);
+        }
+        assert_eq!(level.get_tile(1, y + 4, 0), tile::LEAVES.id);
+        assert!(!level.is_solid_tile(0, y + 8, 0));
+    }
+
+    #[test]
+    fn needs_room_to_grow() {
+        let mut level = Level::with_dir(64, None);
+        level.update_columns(0.0, 0.0);
+        let y = level.depth * 2 / 3 + 1;
+        level.set_tile(0, y, 0, tile::SAPLING.id);
+        level.set_tile(0, y + 2, 0, tile::ROCK.id);
+        grow(&mut level, 0, y, 0);
+        assert_eq!(level.get_tile(0, y, 0), tile::SAPLING.id);
+    }
+}
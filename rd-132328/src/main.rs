@@ -1,13 +1,17 @@
 #[macro_use]
 extern crate lazy_static;
 
-use std::cell::RefCell;
+use std::cell::{RefCell, RefMut};
+use std::path::PathBuf;
 use std::rc::Rc;
 use std::sync::atomic::Ordering;
 use std::time::{Duration, Instant};
 
+use camera::{Camera, CameraMode};
 use character::zombie::Zombie;
 use entity::EntityTrait;
+use falling_tile::FallingTile;
+use gui::hud::Hud;
 use hit_result::HitResult;
 use level::level_renderer::LevelRenderer;
 use lwrgl::glfw::Key;
@@ -15,47 +19,63 @@ use lwrgl::glu_sys::glu;
 use lwrgl::glu_sys::glu::*;
 use lwrgl::LWRGL;
 
-use level::level::Level;
+use level::frustrum::Frustum;
+use level::level::{EntityId, Level};
+use level::tesselator::Tesselator;
+use phys::aabb::AABB;
 use player::Player;
+use saved_entity::SavedEntities;
+use textures::load_texture;
+mod ai;
+mod camera;
 mod character;
 mod entity;
+mod falling_tile;
+#[cfg(test)]
+mod golden_tests;
+mod gui;
 mod hit_result;
 mod level;
 mod phys;
 mod player;
+mod saved_entity;
+mod screenshot;
 mod textures;
 mod timer;
 
-use crate::level::chunk;
+use crate::level::{chunk, tile};
 use crate::timer::Timer;
 
+/// How far away the player can hit entities.
+const REACH: f32 = 4.0;
+const PLAYER_ATTACK_DAMAGE: i32 = 4;
+
 struct RubyDung {
     lwrgl: LWRGL,
     width: i32,
     height: i32,
     fog_color: [GLfloat; 4],
+    screenshot_requested: bool,
     timer: Timer,
     level: Rc<RefCell<Level>>,
     level_renderer: Rc<RefCell<LevelRenderer>>,
-    player: Player,
+    player: EntityId, // among the level's entities, for the game to steer and follow
+    camera: Camera,
     select_buffer: [GLuint; 2000],
     viewport_buffer: [GLint; 16],
     hit_result: Option<HitResult>,
-    zombies: Vec<Zombie>,
+    entity_hit: Option<EntityId>, // looked at and closer than hit_result
+    t: Tesselator,
+    hud: Hud,
+    fps_string: String,
 }
 
 impl RubyDung {
     pub fn new() -> RubyDung {
-        let col = 0x0E0B0A;
         let fr = 0.5;
         let fg = 0.8;
         let fb = 1.0;
-        let fog_color = [
-            (col >> 16 & 0xFF) as f32 / 255.0,
-            (col >> 8 & 0xFF) as f32 / 255.0,
-            (col & 0xFF) as f32 / 255.0,
-            1.0,
-        ];
+        let fog_color = fog_color();
 
         let mut lwrgl = LWRGL::new(1024, 768);
 
@@ -73,15 +93,44 @@ impl RubyDung {
             glLoadIdentity();
             glMatrixMode(GL_MODELVIEW);
 
-            let level = Rc::new(RefCell::new(Level::new(256, 256, 64)));
+            let level = Rc::new(RefCell::new(Level::new(64)));
             let level_renderer = LevelRenderer::new(Rc::clone(&level));
-            let player = Player::new(Rc::clone(&level));
+            let mut player = Player::new(Rc::clone(&level));
+            let saved = level
+                .borrow()
+                .dir()
+                .and_then(|dir| match SavedEntities::load(dir) {
+                    Ok(saved) => saved,
+                    // start over as in a new world rather than not at all
+                    Err(e) => {
+                        eprintln!("Could not read the saved entities: {}", e);
+                        None
+                    }
+                });
+            if let Some(player_saved) = saved.as_ref().and_then(|saved| saved.player.as_ref()) {
+                player.load(player_saved);
+            }
+            level
+                .borrow_mut()
+                .update_columns(player.entity.x, player.entity.z);
+
+            let player = level.borrow_mut().add_entity(player);
 
             lwrgl.grab_mouse();
 
-            let mut zombies = Vec::with_capacity(100);
-            for _ in 0..100 {
-                zombies.push(Zombie::new(Rc::clone(&level), 128.0, 0.0, 128.0));
+            match saved {
+                Some(saved) => {
+                    for entity in &saved.entities {
+                        entity.restore(&level);
+                    }
+                }
+                // a new world
+                None => {
+                    for _ in 0..100 {
+                        let zombie = Zombie::new(Rc::clone(&level), 128.0, 0.0, 128.0);
+                        level.borrow_mut().add_entity(zombie);
+                    }
+                }
             }
 
             RubyDung {
@@ -89,20 +138,50 @@ impl RubyDung {
                 width,
                 height,
                 fog_color,
+                screenshot_requested: false,
                 timer: Timer::new(60.0),
                 level,
                 level_renderer,
                 player,
+                camera: Camera::new(70.0, width as f32 / height as f32),
                 select_buffer: [0; 2000],
                 viewport_buffer: [0; 16],
                 hit_result: None,
-                zombies,
+                entity_hit: None,
+                t: Tesselator::new(),
+                hud: Hud::new(),
+                fps_string: String::new(),
             }
         }
     }
 
     pub fn destroy(&self) {
-        self.level.borrow().save();
+        self.save();
+    }
+
+    /// Saves the level along with the player and the other entities.
+    fn save(&self) {
+        self.level.borrow_mut().save();
+        let level = self.level.borrow();
+        let dir = match level.dir() {
+            Some(dir) => dir,
+            None => return,
+        };
+        let saved = SavedEntities {
+            player: self.player().borrow().save(),
+            entities: level
+                .entities()
+                .into_iter()
+                .filter(|(id, _)| *id != self.player)
+                .filter_map(|(_, entity)| entity.borrow().save())
+                .collect(),
+        };
+        saved.save(dir);
+    }
+
+    /// The player, ticked, pushed and rendered with the rest of the level's entities.
+    fn player(&self) -> Rc<RefCell<dyn EntityTrait>> {
+        self.level.borrow().entity(self.player).unwrap()
     }
 
     pub fn run(&mut self) {
@@ -124,7 +203,11 @@ impl RubyDung {
             frames += 1;
 
             while Instant::now().duration_since(last_time).as_millis() > 1000 {
-                println!("{} fps, {}", frames, chunk::UPDATES.load(Ordering::SeqCst));
+                self.fps_string = format!(
+                    "{} fps, {} chunk updates",
+                    frames,
+                    chunk::UPDATES.load(Ordering::SeqCst)
+                );
                 chunk::UPDATES.store(0, Ordering::SeqCst);
                 last_time = last_time.checked_add(Duration::from_millis(1000)).unwrap();
                 frames = 0;
@@ -135,36 +218,107 @@ impl RubyDung {
     }
 
     pub fn tick(&mut self) {
-        for zombie in &mut self.zombies {
-            zombie.tick(&self.lwrgl);
+        while self.lwrgl.keyboard_next() {
+            if !self.lwrgl.keyboard_event_key_state() {
+                continue;
+            }
+            match self.lwrgl.keyboard_event_key() {
+                Some(Key::F5) => {
+                    let mode = self.camera.mode.next();
+                    self.camera.set_mode(mode);
+                    as_player(&self.player()).controls_enabled = mode != CameraMode::FreeFly;
+                }
+                Some(key) if (Key::Num1 as i32..=Key::Num9 as i32).contains(&(key as i32)) => self
+                    .hud
+                    .hotbar
+                    .select((key as i32 - Key::Num1 as i32) as usize),
+                // the tenth slot, as 0 comes after 9 on the keyboard
+                Some(Key::Num0) => self.hud.hotbar.select(9),
+                Some(Key::Space) => as_player(&self.player()).jump_pressed(),
+                Some(Key::N) => as_player(&self.player()).toggle_noclip(),
+                Some(Key::G) => {
+                    let (x, y, z) = {
+                        let player = self.player();
+                        let player = player.borrow();
+                        (player.entity().x, player.entity().y, player.entity().z)
+                    };
+                    let mut zombie = Zombie::new(Rc::clone(&self.level), 0.0, 0.0, 0.0);
+                    zombie.set_pos(x, y, z);
+                    self.level.borrow_mut().add_entity(zombie);
+                }
+                Some(Key::F2) => self.screenshot_requested = true,
+                Some(Key::F3) => self.hud.show_debug = !self.hud.show_debug,
+                Some(Key::Minus) => self.camera.set_fov(self.camera.fov - 5.0),
+                Some(Key::Equal) => self.camera.set_fov(self.camera.fov + 5.0),
+                _ => {}
+            }
+        }
+
+        self.tick_entities();
+        let hits = self.level.borrow_mut().take_player_hits();
+        let player = self.player();
+        let mut player = as_player(&player);
+        for (damage, x, z) in hits {
+            if player.entity.hurt(damage) {
+                player.entity.knockback(x, z);
+            }
         }
-        self.player.tick(&self.lwrgl);
+        if player.entity.is_dead() {
+            player.respawn();
+        }
+        let (player_x, player_z) = (player.entity.x, player.entity.z);
+        self.level.borrow_mut().player_bb = Some(player.entity.bb);
+        drop(player);
+        self.camera.tick(&self.lwrgl);
+        self.level.borrow_mut().tick();
+        let falling = self.level.borrow_mut().take_falling();
+        for (x, y, z, id) in falling {
+            let falling_tile = FallingTile::new(Rc::clone(&self.level), x, y, z, id);
+            self.level.borrow_mut().add_entity(falling_tile);
+        }
+        self.level.borrow_mut().update_columns(player_x, player_z);
     }
 
-    pub fn move_camera_to_player(&self, a: f32) {
-        unsafe {
-            glTranslatef(0.0, 0.0, -0.3);
-            glRotatef(self.player.entity.x_rot, 1.0, 0.0, 0.0);
-            glRotatef(self.player.entity.y_rot, 0.0, 1.0, 0.0);
-            let x = self.player.entity.xo + (self.player.entity.x - self.player.entity.xo) * a;
-            let y = self.player.entity.yo + (self.player.entity.y - self.player.entity.yo) * a;
-            let z = self.player.entity.zo + (self.player.entity.z - self.player.entity.zo) * a;
-            glTranslatef(-x, -y, -z);
+    /// Ticks the entities in loaded columns and takes out the ones that are done.
+    fn tick_entities(&mut self) {
+        let entities = self.level.borrow().entities();
+        for (id, entity) in entities {
+            // removed by an entity that ticked before it
+            if self.level.borrow().entity(id).is_none() {
+                continue;
+            }
+            let mut entity = entity.borrow_mut();
+            let x = entity.entity().x.floor() as i32;
+            let z = entity.entity().z.floor() as i32;
+            if self.level.borrow().is_loaded(x, z) {
+                entity.tick(&self.lwrgl);
+            }
+            if entity.is_removed() {
+                self.level.borrow_mut().remove_entity(id);
+            } else {
+                let bb = entity.entity().bb;
+                self.level.borrow_mut().entity_moved(id, bb);
+            }
         }
+        self.level.borrow().push_entities_apart();
     }
 
-    pub fn setup_camera(&self, a: f32) {
+    pub fn setup_camera(&mut self, a: f32) {
+        self.camera
+            .update(self.player().borrow().entity(), &self.level.borrow(), a);
         unsafe {
             glMatrixMode(GL_PROJECTION);
-            glLoadIdentity();
-            gluPerspective(70.0, self.width as f64 / self.height as f64, 0.05, 1000.0);
+            glLoadMatrixf(self.camera.projection_matrix().as_ptr());
             glMatrixMode(GL_MODELVIEW);
-            glLoadIdentity();
-            self.move_camera_to_player(a);
+            glLoadMatrixf(self.camera.view_matrix().as_ptr());
         }
     }
 
-    fn setup_pick_camera(&mut self, a: f32, x: i32, y: i32) {
+    /// Sets up picking from the player's eye, returning the camera placed there.
+    fn setup_pick_camera(&mut self, a: f32, x: i32, y: i32) -> Camera {
+        let eye =
+            self.camera
+                .first_person(self.player().borrow().entity(), &self.level.borrow(), a);
         unsafe {
             glMatrixMode(GL_PROJECTION);
             glLoadIdentity();
@@ -180,11 +334,11 @@ impl RubyDung {
                 5.0,
                 self.viewport_buffer.as_mut_ptr(),
             );
-            gluPerspective(70.0, self.width as f64 / self.height as f64, 0.05, 1000.0);
+            glMultMatrixf(eye.projection_matrix().as_ptr());
             glMatrixMode(GL_MODELVIEW);
-            glLoadIdentity();
-            self.move_camera_to_player(a);
+            glLoadMatrixf(eye.view_matrix().as_ptr());
         }
+        eye
     }
 
     pub fn pick(&mut self, a: f32) {
@@ -194,8 +348,10 @@ impl RubyDung {
         unsafe {
             glSelectBuffer(2000, self.select_buffer.as_mut_ptr());
             glRenderMode(GL_SELECT);
-            self.setup_pick_camera(a, self.width / 2, self.height / 2);
-            self.level_renderer.borrow_mut().pick(&self.player);
+            let eye = self.setup_pick_camera(a, self.width / 2, self.height / 2);
+            self.level_renderer
+                .borrow_mut()
+                .pick(self.player().borrow().entity());
             let hits = glRenderMode(GL_RENDER);
             let mut closest = 0;
             let mut names = [0i32; 10];
@@ -227,17 +383,134 @@ impl RubyDung {
             } else {
                 None
             };
+            self.pick_entity(&eye);
         }
     }
 
+    /// Finds the attackable entity in reach in front of the player's `eye`, unless the picked
+    /// tile is in the way.
+    fn pick_entity(&mut self, eye: &Camera) {
+        let origin = [eye.x, eye.y, eye.z];
+        let dir = eye.look_dir();
+        self.entity_hit = None;
+        let (id, t) = match self.level.borrow().pick_entity(origin, dir, REACH) {
+            Some(hit) => hit,
+            None => return,
+        };
+        let tile_t = self.hit_result.as_ref().and_then(|h| {
+            let (x, y, z) = (h.x as f32, h.y as f32, h.z as f32);
+            AABB::new(x, y, z, x + 1.0, y + 1.0, z + 1.0).clip(origin, dir)
+        });
+        if tile_t.is_none_or(|tile_t| t < tile_t) {
+            self.entity_hit = Some(id);
+            self.hit_result = None;
+        }
+    }
+
+    /// Hits the entity with `id`, knocking it away from the player.
+    fn attack(&mut self, id: EntityId) {
+        let entity = match self.level.borrow().entity(id) {
+            Some(entity) => entity,
+            None => return,
+        };
+        let (x, z) = {
+            let player = self.player();
+            let player = player.borrow();
+            (player.entity().x, player.entity().z)
+        };
+        let mut entity = entity.borrow_mut();
+        let entity = entity.entity_mut();
+        if entity.hurt(PLAYER_ATTACK_DAMAGE) {
+            entity.knockback(x, z);
+        }
+    }
+
+    /// Renders the entities in view, grouped by texture so each one is bound only once.
+    unsafe fn render_entities(&mut self, frustum: &Frustum, a: f32) {
+        let mut entities: Vec<_> = self
+            .level
+            .borrow()
+            .entities()
+            .into_iter()
+            // the player is only seen from outside
+            .filter(|(id, _)| *id != self.player || self.camera.mode != CameraMode::FirstPerson)
+            .filter(|(_, entity)| frustum.cube_in_frustum_aabb(&entity.borrow().entity().bb))
+            .map(|(_, entity)| {
+                let texture = entity.borrow().texture().map(str::to_string);
+                (texture, entity)
+            })
+            .collect();
+        entities.sort_by(|(a, _), (b, _)| a.cmp(b));
+        let mut bound = None;
+        for (i, (texture, entity)) in entities.iter().enumerate() {
+            if i == 0 || *texture != bound {
+                match texture {
+                    Some(texture) => {
+                        glEnable(GL_TEXTURE_2D);
+                        glBindTexture(
+                            GL_TEXTURE_2D,
+                            load_texture(texture, GL_NEAREST as i32) as GLuint,
+                        );
+                    }
+                    None => glDisable(GL_TEXTURE_2D),
+                }
+                bound = texture.clone();
+            }
+            entity.borrow_mut().render(&mut self.t, a);
+        }
+        glDisable(GL_TEXTURE_2D);
+    }
+
+    fn debug_lines(&self) -> Vec<String> {
+        let player = self.player();
+        let player = as_player(&player);
+        let flying = player.flying;
+        let player = &player.entity;
+        let level = self.level.borrow();
+        let yaw = player.y_rot.rem_euclid(360.0);
+        let facing = match ((yaw + 45.0) / 90.0) as i32 % 4 {
+            0 => "-z",
+            1 => "+x",
+            2 => "+z",
+            _ => "-x",
+        };
+        let mut lines = vec![
+            self.fps_string.clone(),
+            format!("x: {:.3}", player.x),
+            format!("y: {:.3}", player.y),
+            format!("z: {:.3}", player.z),
+            format!("facing: {} ({:.1} / {:.1})", facing, yaw, player.x_rot),
+            format!("camera: {:?}, fov {}", self.camera.mode, self.camera.fov),
+            format!("flying: {}, noclip: {}", flying, player.noclip),
+            format!(
+                "entities: {}, zombies: {}",
+                level.entities().len(),
+                level.entities_of::<Zombie>().len()
+            ),
+        ];
+        lines.push(match &self.hit_result {
+            Some(h) => format!("looking at: {} {} {}, face {}", h.x, h.y, h.z, h.f),
+            None => "looking at: nothing".to_string(),
+        });
+        lines
+    }
+
     pub fn render(&mut self, a: f32) {
         let xo = self.lwrgl.mouse_dx();
         let yo = self.lwrgl.mouse_dy();
 
-        self.player.turn(xo as f32, yo as f32);
+        if self.camera.mode == CameraMode::FreeFly {
+            self.camera.turn(xo as f32, yo as f32);
+        } else {
+            self.player().borrow_mut().turn(xo as f32, yo as f32);
+        }
         self.pick(a);
 
         while self.lwrgl.mouse_next() {
+            let dwheel = self.lwrgl.mouse_event_dwheel();
+            if dwheel != 0 {
+                self.hud.hotbar.scroll(-dwheel);
+            }
             if self.lwrgl.mouse_event_button() == 1 && self.lwrgl.mouse_event_button_state() {
                 if let Some(hit_result) = &self.hit_result {
                     self.level
@@ -245,10 +518,11 @@ impl RubyDung {
                         .set_tile(hit_result.x, hit_result.y, hit_result.z, 0);
                 }
             }
-            if self.lwrgl.mouse_event_button() != 0
-                || !self.lwrgl.mouse_event_button_state()
-                || self.hit_result.is_none()
-            {
+            if self.lwrgl.mouse_event_button() != 0 || !self.lwrgl.mouse_event_button_state() {
+                continue;
+            }
+            if let Some(id) = self.entity_hit {
+                self.attack(id);
                 continue;
             }
             if let Some(hit_result) = &self.hit_result {
@@ -275,12 +549,26 @@ impl RubyDung {
                     x += 1;
                 }
 
-                self.level.borrow_mut().set_tile(x, y, z, 1);
+                let id = self.hud.hotbar.selected_tile();
+                let bb = AABB::new(
+                    x as f32,
+                    y as f32,
+                    z as f32,
+                    (x + 1) as f32,
+                    (y + 1) as f32,
+                    (z + 1) as f32,
+                );
+                // solid tiles are not placed inside anything
+                let blocked = tile::by_id(id).is_some_and(|tile| tile.is_solid())
+                    && !self.level.borrow().entities_in(&bb).is_empty();
+                if !blocked {
+                    self.level.borrow_mut().set_tile(x, y, z, id);
+                }
             }
         }
 
         if self.lwrgl.is_key_down(Key::Enter) {
-            self.level.borrow().save();
+            self.save();
         }
 
         unsafe {
@@ -292,23 +580,99 @@ impl RubyDung {
             glFogf(GL_FOG_DENSITY, 0.2);
             glFogfv(GL_FOG_COLOR, self.fog_color.as_ptr());
             glDisable(GL_FOG);
-            self.level_renderer.borrow_mut().render(&self.player, 0);
-            for zombie in &mut self.zombies {
-                zombie.render(a);
-            }
+            let frustum = self.camera.frustum();
+            self.level_renderer.borrow_mut().render(&frustum, 0);
+            self.render_entities(&frustum, a);
+            glEnable(GL_FOG);
+            self.level_renderer.borrow_mut().render(&frustum, 1);
+            glDisable(GL_FOG);
+            glEnable(GL_BLEND);
+            glBlendFunc(GL_SRC_ALPHA, GL_ONE_MINUS_SRC_ALPHA);
+            self.level_renderer
+                .borrow_mut()
+                .render(&frustum, tile::TRANSLUCENT_LAYER);
+            glDisable(GL_BLEND);
             glEnable(GL_FOG);
-            self.level_renderer.borrow_mut().render(&self.player, 1);
             glDisable(GL_TEXTURE_2D);
             if let Some(hit_result) = &self.hit_result {
                 self.level_renderer.borrow_mut().render_hit(hit_result);
             }
             glDisable(GL_FOG);
+            let debug_lines = self.debug_lines();
+            self.hud.render(
+                self.width,
+                self.height,
+                self.player().borrow().entity().health,
+                &debug_lines,
+            );
+            if self.screenshot_requested {
+                // read the finished frame before it is swapped out
+                let path = screenshot::take_screenshot(self.width, self.height);
+                println!("Saved screenshot as {}", path.display());
+                self.screenshot_requested = false;
+            }
             self.lwrgl.update();
         }
     }
 }
 
+/// `entity` as the player it is.
+fn as_player(entity: &RefCell<dyn EntityTrait>) -> RefMut<'_, Player> {
+    RefMut::map(entity.borrow_mut(), |entity| {
+        entity.as_any_mut().downcast_mut().unwrap()
+    })
+}
+
+pub fn fog_color() -> [GLfloat; 4] {
+    let col = 0x0E0B0A;
+    [
+        (col >> 16 & 0xFF) as f32 / 255.0,
+        (col >> 8 & 0xFF) as f32 / 255.0,
+        (col & 0xFF) as f32 / 255.0,
+        1.0,
+    ]
+}
+
+/// `--render-frame <world> <x> <y> <z> <y_rot> <x_rot> <width> <height> <out.png>` renders one
+/// frame of the world saved in the `<world>` directory without opening a visible window.
+fn render_frame(args: &[String]) {
+    let usage = || -> ! {
+        eprintln!(
+            "usage: --render-frame <world> <x> <y> <z> <y_rot> <x_rot> <width> <height> <out.png>"
+        );
+        std::process::exit(1);
+    };
+    if args.len() != 9 {
+        usage();
+    }
+    let num = |i: usize| args[i + 1].parse::<f32>().unwrap_or_else(|_| usage());
+    // a whole number of pixels, and at least one
+    let size = |i: usize| match args[i + 1].parse::<u32>() {
+        Ok(size) if size > 0 && size <= i32::MAX as u32 => size as i32,
+        _ => usage(),
+    };
+    let (width, height) = (size(5), size(6));
+    let mut camera = Camera::new(70.0, 1.0);
+    camera.x = num(0);
+    camera.y = num(1);
+    camera.z = num(2);
+    camera.y_rot = num(3);
+    camera.x_rot = num(4);
+    camera.distance = 0.0;
+    let level = Rc::new(RefCell::new(Level::with_dir(
+        64,
+        Some(PathBuf::from(&args[0])),
+    )));
+    let img = screenshot::render_frame(level, &camera, width, height);
+    img.save(&args[8]).unwrap();
+}
+
 pub fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() > 1 && args[1] == "--render-frame" {
+        render_frame(&args[2..]);
+        return;
+    }
     let mut rd = RubyDung::new();
     rd.run();
 }
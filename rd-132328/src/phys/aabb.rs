@@ -1,6 +1,6 @@
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 pub struct AABB {
-    epsilon: f32,
+    epsilon: f32, // gap left between a box and what it is clipped against
     pub x0: f32,
     pub y0: f32,
     pub z0: f32,
@@ -22,6 +22,16 @@ impl AABB {
         }
     }
 
+    /// The same box, keeping `epsilon` away from boxes clipped against it.
+    pub fn with_epsilon(mut self, epsilon: f32) -> AABB {
+        self.epsilon = epsilon;
+        self
+    }
+
+    pub fn epsilon(&self) -> f32 {
+        self.epsilon
+    }
+
     pub fn expand(&self, xa: f32, ya: f32, za: f32) -> AABB {
         let mut _x0 = self.x0;
         let mut _y0 = self.y0;
@@ -47,7 +57,7 @@ impl AABB {
         if za > 0.0 {
             _z1 += za;
         }
-        AABB::new(_x0, _y0, _z0, _x1, _y1, _z1)
+        AABB::new(_x0, _y0, _z0, _x1, _y1, _z1).with_epsilon(self.epsilon)
     }
 
     pub fn grow(&self, xa: f32, ya: f32, za: f32) -> AABB {
@@ -57,7 +67,7 @@ impl AABB {
         let mut _x1 = self.x1 + xa;
         let mut _y1 = self.y1 + ya;
         let mut _z1 = self.z1 + za;
-        AABB::new(_x0, _y0, _z0, _x1, _y1, _z1)
+        AABB::new(_x0, _y0, _z0, _x1, _y1, _z1).with_epsilon(self.epsilon)
     }
 
     pub fn clip_x_collide(&self, c: &AABB, xa: f32) -> f32 {
@@ -117,6 +127,38 @@ impl AABB {
         za
     }
 
+    pub fn intersects(&self, c: &AABB) -> bool {
+        c.x1 > self.x0
+            && c.x0 < self.x1
+            && c.y1 > self.y0
+            && c.y0 < self.y1
+            && c.z1 > self.z0
+            && c.z0 < self.z1
+    }
+
+    /// How far along `dir` a ray from `origin` enters the box, in lengths of `dir`. `None` if it
+    /// misses it or the box is behind the origin.
+    pub fn clip(&self, origin: [f32; 3], dir: [f32; 3]) -> Option<f32> {
+        let bounds = [(self.x0, self.x1), (self.y0, self.y1), (self.z0, self.z1)];
+        let mut t_enter = 0.0f32;
+        let mut t_exit = f32::INFINITY;
+        for ((b0, b1), (o, d)) in bounds.into_iter().zip(origin.into_iter().zip(dir)) {
+            if d == 0.0 {
+                if o < b0 || o > b1 {
+                    return None;
+                }
+                continue;
+            }
+            let (t0, t1) = ((b0 - o) / d, (b1 - o) / d);
+            t_enter = t_enter.max(t0.min(t1));
+            t_exit = t_exit.min(t0.max(t1));
+        }
+        if t_enter > t_exit {
+            return None;
+        }
+        Some(t_enter)
+    }
+
     pub fn move_(&mut self, xa: f32, ya: f32, za: f32) {
         self.x0 += xa;
         self.y0 += ya;
@@ -126,3 +168,192 @@ impl AABB {
         self.z1 += za;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::AABB;
+    use proptest::prelude::*;
+
+    // float slack when checking positions that were clipped to touch exactly
+    const TOLERANCE: f32 = 1e-4;
+
+    fn cube(x: f32, y: f32, z: f32) -> AABB {
+        AABB::new(x, y, z, x + 1.0, y + 1.0, z + 1.0)
+    }
+
+    #[test]
+    fn rays_clip_the_near_side() {
+        let c = cube(2.0, 0.0, 0.0);
+        assert_eq!(c.clip([0.0, 0.5, 0.5], [1.0, 0.0, 0.0]), Some(2.0));
+        assert_eq!(c.clip([0.0, 0.5, 0.5], [2.0, 0.0, 0.0]), Some(1.0));
+        assert_eq!(c.clip([2.5, 0.5, 0.5], [1.0, 0.0, 0.0]), Some(0.0));
+        assert_eq!(c.clip([0.0, 0.5, 0.5], [-1.0, 0.0, 0.0]), None);
+        assert_eq!(c.clip([0.0, 2.5, 0.5], [1.0, 0.0, 0.0]), None);
+        let t = c.clip([0.0, -1.0, 0.5], [1.0, 1.0, 0.0]).unwrap();
+        assert!((t - 2.0).abs() < 1e-6);
+    }
+
+    fn overlaps(a: &AABB, b: &AABB) -> bool {
+        a.x0 < b.x1 - TOLERANCE
+            && a.x1 > b.x0 + TOLERANCE
+            && a.y0 < b.y1 - TOLERANCE
+            && a.y1 > b.y0 + TOLERANCE
+            && a.z0 < b.z1 - TOLERANCE
+            && a.z1 > b.z0 + TOLERANCE
+    }
+
+    #[test]
+    fn expand_only_grows_towards_the_motion() {
+        let bb = cube(0.0, 0.0, 0.0).expand(2.0, -3.0, 0.0);
+        assert_eq!((bb.x0, bb.y0, bb.z0), (0.0, -3.0, 0.0));
+        assert_eq!((bb.x1, bb.y1, bb.z1), (3.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn grow_grows_on_both_sides() {
+        let bb = cube(0.0, 0.0, 0.0).grow(1.0, 2.0, 3.0);
+        assert_eq!((bb.x0, bb.y0, bb.z0), (-1.0, -2.0, -3.0));
+        assert_eq!((bb.x1, bb.y1, bb.z1), (2.0, 3.0, 4.0));
+    }
+
+    #[test]
+    fn clips_motion_towards_a_box() {
+        let c = cube(3.0, 0.0, 0.0);
+        let bb = cube(0.0, 0.0, 0.0);
+        assert_eq!(c.clip_x_collide(&bb, 5.0), 2.0);
+        assert_eq!(c.clip_x_collide(&bb, 1.5), 1.5);
+        assert_eq!(c.clip_x_collide(&bb, -5.0), -5.0);
+    }
+
+    #[test]
+    fn lands_on_a_box_below() {
+        let ground = cube(0.0, 0.0, 0.0);
+        let bb = AABB::new(0.2, 1.5, 0.2, 0.8, 3.3, 0.8);
+        assert_eq!(ground.clip_y_collide(&bb, -2.0), -0.5);
+        assert_eq!(ground.clip_y_collide(&bb, 2.0), 2.0);
+    }
+
+    #[test]
+    fn ignores_boxes_beside_the_path() {
+        let c = cube(0.0, 0.0, 5.0);
+        let bb = cube(0.0, 0.0, 0.0);
+        assert_eq!(c.clip_x_collide(&bb, 10.0), 10.0);
+        assert_eq!(c.clip_y_collide(&bb, -10.0), -10.0);
+        // only touching along an edge is not in the way either
+        let c = cube(1.0, 1.0, 0.0);
+        assert_eq!(c.clip_x_collide(&bb, 10.0), 10.0);
+    }
+
+    #[test]
+    fn epsilon_keeps_a_gap() {
+        let c = cube(3.0, 0.0, 0.0).with_epsilon(0.25);
+        let bb = cube(0.0, 0.0, 0.0);
+        assert_eq!(c.clip_x_collide(&bb, 5.0), 1.75);
+        assert_eq!(c.clip_z_collide(&bb, 5.0), 5.0);
+        assert_eq!(c.grow(1.0, 1.0, 1.0).clip_x_collide(&bb, 5.0), 0.75);
+    }
+
+    fn any_box() -> impl Strategy<Value = AABB> {
+        (
+            -4.0f32..4.0,
+            -4.0f32..4.0,
+            -4.0f32..4.0,
+            0.1f32..2.0,
+            0.1f32..2.0,
+            0.1f32..2.0,
+        )
+            .prop_map(|(x, y, z, w, h, d)| AABB::new(x, y, z, x + w, y + h, z + d))
+    }
+
+    fn clip(c: &AABB, bb: &AABB, axis: usize, a: f32) -> f32 {
+        match axis {
+            0 => c.clip_x_collide(bb, a),
+            1 => c.clip_y_collide(bb, a),
+            _ => c.clip_z_collide(bb, a),
+        }
+    }
+
+    fn moved(bb: &AABB, axis: usize, a: f32) -> AABB {
+        let mut bb = *bb;
+        match axis {
+            0 => bb.move_(a, 0.0, 0.0),
+            1 => bb.move_(0.0, a, 0.0),
+            _ => bb.move_(0.0, 0.0, a),
+        }
+        bb
+    }
+
+    proptest! {
+        #[test]
+        fn clipped_motion_never_overlaps(
+            c in any_box(),
+            bb in any_box(),
+            axis in 0..3usize,
+            a in -10.0f32..10.0,
+        ) {
+            prop_assume!(!overlaps(&c, &bb));
+            let clipped = clip(&c, &bb, axis, a);
+            prop_assert!(!overlaps(&c, &moved(&bb, axis, clipped)));
+        }
+
+        #[test]
+        fn clipping_only_shortens_motion(
+            c in any_box(),
+            bb in any_box(),
+            axis in 0..3usize,
+            a in -10.0f32..10.0,
+        ) {
+            prop_assume!(!overlaps(&c, &bb));
+            let clipped = clip(&c, &bb, axis, a);
+            prop_assert!(clipped.abs() <= a.abs());
+            prop_assert!(clipped * a >= 0.0);
+        }
+
+        #[test]
+        fn swept_motion_is_monotone(
+            c in any_box(),
+            bb in any_box(),
+            axis in 0..3usize,
+            a in -10.0f32..10.0,
+            b in -10.0f32..10.0,
+        ) {
+            prop_assume!(!overlaps(&c, &bb));
+            let (lo, hi) = if a < b { (a, b) } else { (b, a) };
+            prop_assert!(clip(&c, &bb, axis, lo) <= clip(&c, &bb, axis, hi));
+        }
+
+        #[test]
+        fn epsilon_gap_is_kept(
+            bb in any_box(),
+            axis in 0..3usize,
+            a in -10.0f32..10.0,
+            epsilon in 0.0f32..0.1,
+        ) {
+            let c = cube(0.0, 0.0, 0.0).with_epsilon(epsilon);
+            prop_assume!(!overlaps(&c, &bb));
+            let clipped = clip(&c, &bb, axis, a);
+            let grown = c.grow(epsilon, epsilon, epsilon);
+            // moving into the box stops `epsilon` short of it
+            if clipped != a {
+                prop_assert!(!overlaps(&grown, &moved(&bb, axis, clipped)));
+            }
+        }
+
+        #[test]
+        fn expand_covers_the_motion(
+            bb in any_box(),
+            xa in -5.0f32..5.0,
+            ya in -5.0f32..5.0,
+            za in -5.0f32..5.0,
+        ) {
+            let expanded = bb.expand(xa, ya, za);
+            let mut end = bb;
+            end.move_(xa, ya, za);
+            for b in [&bb, &end] {
+                prop_assert!(expanded.x0 <= b.x0 && b.x1 <= expanded.x1);
+                prop_assert!(expanded.y0 <= b.y0 && b.y1 <= expanded.y1);
+                prop_assert!(expanded.z0 <= b.z0 && b.z1 <= expanded.z1);
+            }
+        }
+    }
+}
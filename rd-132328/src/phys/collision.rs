@@ -0,0 +1,299 @@
+use super::aabb::AABB;
+
+/// Where a resolved move ends, relative to where it started, and what it ran into.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Collision {
+    pub xa: f32,
+    pub ya: f32,
+    pub za: f32,
+    /// Per axis, the direction of the faces that stopped the move, 0.0 if none did.
+    pub normal: [f32; 3],
+}
+
+impl Collision {
+    pub fn on_ground(&self) -> bool {
+        self.normal[1] > 0.0
+    }
+}
+
+/// Moves `bb` by `xa`/`ya`/`za` through `cubes`, stopping at the first face it hits and sliding
+/// along it with what is left of the motion. The axes are clipped in the order the box runs into
+/// things rather than always y, x, z, so the result does not depend on how far it moves in one go.
+///
+/// A box standing on the ground, or landing on it during this move, climbs ledges of up to
+/// `step_height` when that gets it further.
+pub fn resolve(
+    cubes: &[AABB],
+    bb: &AABB,
+    xa: f32,
+    ya: f32,
+    za: f32,
+    step_height: f32,
+    on_ground: bool,
+) -> Collision {
+    let collision = slide(cubes, bb, [xa, ya, za]);
+    let blocked = collision.normal[0] != 0.0 || collision.normal[2] != 0.0;
+    if step_height <= 0.0 || !blocked || !(on_ground || collision.on_ground()) {
+        return collision;
+    }
+
+    let up = slide(cubes, bb, [0.0, step_height, 0.0]);
+    let mut stepped = *bb;
+    stepped.move_(0.0, up.ya, 0.0);
+    let across = slide(cubes, &stepped, [xa, 0.0, za]);
+    stepped.move_(across.xa, 0.0, across.za);
+    let down = slide(cubes, &stepped, [0.0, -up.ya, 0.0]);
+
+    let dist = collision.xa * collision.xa + collision.za * collision.za;
+    let stepped_dist = across.xa * across.xa + across.za * across.za;
+    if stepped_dist <= dist {
+        return collision;
+    }
+    Collision {
+        xa: across.xa,
+        ya: up.ya + down.ya,
+        za: across.za,
+        normal: [across.normal[0], down.normal[1], across.normal[2]],
+    }
+}
+
+fn slide(cubes: &[AABB], bb: &AABB, motion: [f32; 3]) -> Collision {
+    let mut bb = *bb;
+    let mut motion = motion;
+    let mut moved = [0.0; 3];
+    let mut normal = [0.0; 3];
+    // every hit stops the motion along one axis, so three are enough
+    for _ in 0..3 {
+        let hit = cubes
+            .iter()
+            .filter_map(|c| sweep(&bb, c, motion))
+            .min_by(|a, b| a.0.total_cmp(&b.0));
+        let (t, axis) = match hit {
+            Some(hit) => hit,
+            None => {
+                advance(&mut bb, &mut moved, motion);
+                break;
+            }
+        };
+        // nothing is in the way along the other axes up to the hit
+        let mut step = motion.map(|m| m * t);
+        step[axis] = 0.0;
+        advance(&mut bb, &mut moved, step);
+        let a = cubes
+            .iter()
+            .fold(motion[axis], |a, c| clip(c, &bb, axis, a));
+        if a != motion[axis] {
+            normal[axis] = -motion[axis].signum();
+        }
+        let mut step = [0.0; 3];
+        step[axis] = a;
+        advance(&mut bb, &mut moved, step);
+        motion = motion.map(|m| m * (1.0 - t));
+        motion[axis] = 0.0;
+    }
+    Collision {
+        xa: moved[0],
+        ya: moved[1],
+        za: moved[2],
+        normal,
+    }
+}
+
+fn advance(bb: &mut AABB, moved: &mut [f32; 3], step: [f32; 3]) {
+    bb.move_(step[0], step[1], step[2]);
+    for (moved, step) in moved.iter_mut().zip(step) {
+        *moved += step;
+    }
+}
+
+fn clip(c: &AABB, bb: &AABB, axis: usize, a: f32) -> f32 {
+    match axis {
+        0 => c.clip_x_collide(bb, a),
+        1 => c.clip_y_collide(bb, a),
+        _ => c.clip_z_collide(bb, a),
+    }
+}
+
+/// When during `motion` `bb` first runs into `c`, as the fraction of the motion, and the axis of
+/// the face it hits. A box already inside the `epsilon` gap in front of the face hits it right
+/// away.
+fn sweep(bb: &AABB, c: &AABB, motion: [f32; 3]) -> Option<(f32, usize)> {
+    let b = [(bb.x0, bb.x1), (bb.y0, bb.y1), (bb.z0, bb.z1)];
+    let s = [(c.x0, c.x1), (c.y0, c.y1), (c.z0, c.z1)];
+    let mut t_enter = f32::NEG_INFINITY;
+    let mut t_exit = f32::INFINITY;
+    let mut axis = None;
+    let mut gap = 0.0; // to the face itself along that axis
+    for i in 0..3 {
+        let (b0, b1) = b[i];
+        let (c0, c1) = s[i];
+        let m = motion[i];
+        if m == 0.0 {
+            if b1 <= c0 || b0 >= c1 {
+                return None;
+            }
+            continue;
+        }
+        // distances to start and stop overlapping along this axis
+        let (enter, exit) = if m > 0.0 {
+            (c0 - b1 - c.epsilon(), c1 - b0)
+        } else {
+            (b0 - c1 - c.epsilon(), b1 - c0)
+        };
+        let t0 = enter / m.abs();
+        if t0 > t_enter {
+            t_enter = t0;
+            axis = Some(i);
+            gap = enter + c.epsilon();
+        }
+        t_exit = t_exit.min(exit / m.abs());
+    }
+    let axis = axis?;
+    if t_enter < 0.0 && gap >= 0.0 {
+        t_enter = 0.0;
+    }
+    if !(0.0..=1.0).contains(&t_enter) || t_enter >= t_exit {
+        return None;
+    }
+    Some((t_enter, axis))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cube(x: f32, y: f32, z: f32) -> AABB {
+        AABB::new(x, y, z, x + 1.0, y + 1.0, z + 1.0)
+    }
+
+    fn floor() -> Vec<AABB> {
+        let mut cubes = vec![];
+        for x in -3..4 {
+            for z in -3..4 {
+                cubes.push(cube(x as f32, -1.0, z as f32));
+            }
+        }
+        cubes
+    }
+
+    fn player_at(x: f32, y: f32, z: f32) -> AABB {
+        AABB::new(x - 0.3, y, z - 0.3, x + 0.3, y + 1.8, z + 0.3)
+    }
+
+    #[test]
+    fn falls_onto_the_floor_however_fast() {
+        let floor = [cube(0.0, 0.0, 0.0)];
+        let c = resolve(
+            &floor,
+            &player_at(0.5, 50.0, 0.5),
+            0.0,
+            -500.0,
+            0.0,
+            0.0,
+            false,
+        );
+        assert_eq!(c.ya, -49.0);
+        assert!(c.on_ground());
+    }
+
+    #[test]
+    fn moves_freely_without_cubes() {
+        let c = resolve(&[], &player_at(0.0, 0.0, 0.0), 1.0, 2.0, 3.0, 0.5, true);
+        assert_eq!((c.xa, c.ya, c.za), (1.0, 2.0, 3.0));
+        assert_eq!(c.normal, [0.0; 3]);
+    }
+
+    #[test]
+    fn walks_across_seams_between_cubes() {
+        let c = resolve(
+            &floor(),
+            &player_at(-2.5, 0.0, 0.5),
+            5.0,
+            -0.01,
+            0.0,
+            0.0,
+            true,
+        );
+        assert_eq!((c.xa, c.ya), (5.0, 0.0));
+        assert_eq!(c.normal, [0.0, 1.0, 0.0]);
+    }
+
+    #[test]
+    fn slides_along_a_wall() {
+        let mut cubes = floor();
+        cubes.push(cube(1.0, 0.0, 0.0));
+        let c = resolve(&cubes, &player_at(0.5, 0.0, 0.5), 1.0, 0.0, 0.5, 0.0, true);
+        assert!((c.xa - 0.2).abs() < 1e-6);
+        assert!((c.za - 0.5).abs() < 1e-6);
+        assert_eq!(c.normal, [-1.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn steps_up_a_ledge() {
+        let mut cubes = floor();
+        cubes.push(AABB::new(1.0, 0.0, 0.0, 2.0, 0.5, 1.0));
+        let c = resolve(
+            &cubes,
+            &player_at(0.5, 0.0, 0.5),
+            0.5,
+            -0.01,
+            0.0,
+            0.5,
+            true,
+        );
+        assert_eq!((c.xa, c.ya), (0.5, 0.5));
+        assert!(c.on_ground());
+        // not without a step height or in the air
+        let c = resolve(&cubes, &player_at(0.5, 0.0, 0.5), 0.5, 0.0, 0.0, 0.0, true);
+        assert_eq!(c.ya, 0.0);
+        let c = resolve(&cubes, &player_at(0.5, 0.0, 0.5), 0.5, 0.0, 0.0, 0.5, false);
+        assert_eq!(c.ya, 0.0);
+    }
+
+    #[test]
+    fn does_not_step_up_a_full_block_with_half_a_step() {
+        let mut cubes = floor();
+        cubes.push(cube(1.0, 0.0, 0.0));
+        let c = resolve(
+            &cubes,
+            &player_at(0.5, 0.0, 0.5),
+            0.5,
+            -0.01,
+            0.0,
+            0.5,
+            true,
+        );
+        assert_eq!(c.ya, 0.0);
+        assert_eq!(c.normal, [-1.0, 1.0, 0.0]);
+    }
+
+    #[test]
+    fn keeps_the_epsilon_gap() {
+        let cubes = [cube(0.0, 0.0, 0.0).with_epsilon(0.1)];
+        let c = resolve(
+            &cubes,
+            &player_at(0.5, 3.0, 0.5),
+            0.0,
+            -5.0,
+            0.0,
+            0.0,
+            false,
+        );
+        assert!((c.ya + 1.9).abs() < 1e-6);
+    }
+
+    #[test]
+    fn rests_inside_the_epsilon_gap() {
+        let cubes = [cube(0.0, 0.0, 0.0).with_epsilon(0.1)];
+        let mut bb = player_at(0.5, 1.05, 0.5);
+        let mut ya = 0.0;
+        for _ in 0..100 {
+            ya -= 0.005;
+            let c = resolve(&cubes, &bb, 0.0, ya, 0.0, 0.0, false);
+            bb.move_(c.xa, c.ya, c.za);
+            assert!(c.on_ground());
+            ya = 0.0;
+        }
+        assert!(bb.y0 >= 1.05 - 1e-6 && bb.y0 <= 1.1 + 1e-6);
+    }
+}
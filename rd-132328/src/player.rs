@@ -1,24 +1,158 @@
+use std::any::Any;
 use std::cell::RefCell;
 use std::rc::Rc;
 
-use crate::entity::{Entity, EntityTrait};
+use crate::character::animation::Animator;
+use crate::character::model::Model;
+use crate::entity::{Entity, EntityTrait, AIR_DRAG, GRAVITY, JUMP_SPEED, MAX_HEALTH};
 use crate::level::level::Level;
+use crate::level::tesselator::Tesselator;
+use crate::saved_entity::SavedEntity;
 use lwrgl::glfw::Key;
+use lwrgl::glu_sys::{glColor3f, glPopMatrix, glPushMatrix, glRotatef, glScalef, glTranslatef};
 use lwrgl::LWRGL;
 
+/// How much slower a sneaking player walks.
+const SNEAK_SPEED: f32 = 0.3;
+/// Pressing jump twice within this many ticks toggles flying.
+const DOUBLE_TAP_TICKS: i32 = 7;
+const FLY_SPEED: f32 = 0.05;
+const FLY_VERTICAL_SPEED: f32 = 0.15;
+/// Upwards push while holding jump in a fluid.
+const SWIM_SPEED: f32 = 0.01;
+/// Distance moved in a tick at which the walk animation plays at full weight.
+const WALK_ANIMATION_SPEED: f32 = 0.05;
+
 pub struct Player {
     pub entity: Entity,
+    pub controls_enabled: bool, // false while the camera is detached
+    pub flying: bool,
+    jump_tap_ticks: i32, // ticks left to press jump a second time
+    // only seen from outside, when the camera is not in first person
+    model: Model,
+    animator: Animator,
 }
 
 impl Player {
     pub fn new(level: Rc<RefCell<Level>>) -> Player {
         let mut entity = Entity::new(level);
         entity.height_offset = 1.62;
-        Player { entity }
+        let model = Model::load("player.json");
+        let animator = Animator::new(&model, &["walk"], 0.0);
+        Player {
+            entity,
+            controls_enabled: true,
+            flying: false,
+            jump_tap_ticks: 0,
+            model,
+            animator,
+        }
+    }
+
+    /// Called for every press of the jump key, a double tap toggles flying.
+    pub fn jump_pressed(&mut self) {
+        if !self.controls_enabled {
+            return;
+        }
+        if self.jump_tap_ticks > 0 {
+            self.flying = !self.flying;
+            self.jump_tap_ticks = 0;
+        } else {
+            self.jump_tap_ticks = DOUBLE_TAP_TICKS;
+        }
+    }
+
+    /// Starts over at a new spawn point with full health.
+    pub fn respawn(&mut self) {
+        let this = &mut self.entity;
+        this.reset_pos();
+        this.xd = 0.0;
+        this.yd = 0.0;
+        this.zd = 0.0;
+        this.health = MAX_HEALTH;
+        this.hurt_time = 0;
+        this.fall_distance = 0.0;
+        self.flying = false;
+    }
+
+    /// Picks up where the player saved as `saved` left off.
+    pub fn load(&mut self, saved: &SavedEntity) {
+        if let SavedEntity::Player {
+            entity,
+            flying,
+            noclip,
+        } = saved
+        {
+            self.entity.load(entity);
+            self.flying = *flying;
+            self.entity.noclip = *noclip;
+        }
+    }
+
+    /// Toggles flying through tiles, which implies flying.
+    pub fn toggle_noclip(&mut self) {
+        self.entity.noclip = !self.entity.noclip;
+        if self.entity.noclip {
+            self.flying = true;
+        }
+    }
+
+    /// Moves the flying player for a tick, `up` being -1, 0 or 1 for going down, staying level or
+    /// going up.
+    fn fly(&mut self, xa: f32, ya: f32, up: f32) {
+        let this = &mut self.entity;
+        this.fall_distance = 0.0;
+        this.move_relative(xa, ya, FLY_SPEED);
+        this.yd += up * FLY_VERTICAL_SPEED;
+        this.move_(this.xd, this.yd, this.zd);
+        this.xd *= 0.91;
+        this.yd *= 0.6;
+        this.zd *= 0.91;
+        // landing ends the flight, unless flying through tiles
+        if this.on_ground && !this.noclip {
+            self.flying = false;
+        }
+    }
+
+    fn animate(&mut self) {
+        let this = &self.entity;
+        let xd = this.x - this.xo;
+        let zd = this.z - this.zo;
+        self.animator
+            .set_weight("walk", (xd * xd + zd * zd).sqrt() / WALK_ANIMATION_SPEED);
+        self.animator.tick();
     }
 }
 
 impl EntityTrait for Player {
+    fn entity(&self) -> &Entity {
+        &self.entity
+    }
+
+    fn entity_mut(&mut self) -> &mut Entity {
+        &mut self.entity
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn is_pushable(&self) -> bool {
+        true
+    }
+
+    fn save(&self) -> Option<SavedEntity> {
+        Some(SavedEntity::Player {
+            entity: self.entity.data(),
+            flying: self.flying,
+            noclip: self.entity.noclip,
+        })
+    }
+
     fn reset_pos(&mut self) {
         self.entity.reset_pos()
     }
@@ -36,37 +170,74 @@ impl EntityTrait for Player {
         this.xo = this.x;
         this.yo = this.y;
         this.zo = this.z;
-        let mut xa = 0.0;
-        let mut ya = 0.0;
-        if lwrgl.is_key_down(Key::R) {
-            this.reset_pos();
+        this.y_rot_o = this.y_rot;
+        this.x_rot_o = this.x_rot;
+        if self.jump_tap_ticks > 0 {
+            self.jump_tap_ticks -= 1;
         }
-        if lwrgl.is_key_down(Key::Up) || lwrgl.is_key_down(Key::W) {
-            ya -= 1.0;
+        if this.hurt_time > 0 {
+            this.hurt_time -= 1;
         }
-        if lwrgl.is_key_down(Key::Down) || lwrgl.is_key_down(Key::S) {
-            ya += 1.0;
-        }
-        if lwrgl.is_key_down(Key::Left) || lwrgl.is_key_down(Key::A) {
-            xa -= 1.0;
+        let mut xa = 0.0;
+        let mut ya = 0.0;
+        let mut up = 0.0;
+        let jumping = lwrgl.is_key_down(Key::Space) || lwrgl.is_key_down(Key::LeftSuper);
+        let shift = lwrgl.is_key_down(Key::LeftShift);
+        this.sneaking = self.controls_enabled && shift && !self.flying;
+        if self.controls_enabled {
+            if lwrgl.is_key_down(Key::R) {
+                this.reset_pos();
+            }
+            if lwrgl.is_key_down(Key::Up) || lwrgl.is_key_down(Key::W) {
+                ya -= 1.0;
+            }
+            if lwrgl.is_key_down(Key::Down) || lwrgl.is_key_down(Key::S) {
+                ya += 1.0;
+            }
+            if lwrgl.is_key_down(Key::Left) || lwrgl.is_key_down(Key::A) {
+                xa -= 1.0;
+            }
+            if lwrgl.is_key_down(Key::Right) || lwrgl.is_key_down(Key::D) {
+                xa += 1.0;
+            }
+            if jumping {
+                up += 1.0;
+            }
+            if shift {
+                up -= 1.0;
+            }
+            if jumping && !self.flying {
+                if this.in_fluid.is_some() {
+                    this.yd += SWIM_SPEED;
+                } else if this.on_ground {
+                    this.yd = JUMP_SPEED;
+                }
+            }
         }
-        if lwrgl.is_key_down(Key::Right) || lwrgl.is_key_down(Key::D) {
-            xa += 1.0;
+        if self.flying {
+            self.fly(xa, ya, up);
+            self.animate();
+            return;
         }
-        if (lwrgl.is_key_down(Key::Space) || lwrgl.is_key_down(Key::LeftSuper)) && this.on_ground {
-            this.yd = 0.12;
+        let mut speed = if this.on_ground || this.in_fluid.is_some() {
+            0.02
+        } else {
+            0.005
+        };
+        if this.sneaking {
+            speed *= SNEAK_SPEED;
         }
-        let speed = if this.on_ground { 0.02 } else { 0.005 };
         this.move_relative(xa, ya, speed);
-        this.yd = (this.yd as f64 - 0.005) as f32;
+        this.yd -= GRAVITY;
         this.move_(this.xd, this.yd, this.zd);
         this.xd *= 0.91;
-        this.yd *= 0.98;
+        this.yd *= AIR_DRAG;
         this.zd *= 0.91;
         if this.on_ground {
             this.xd *= 0.8;
             this.zd *= 0.8;
         }
+        self.animate();
     }
 
     fn move_(&mut self, xa: f32, ya: f32, za: f32) {
@@ -76,4 +247,96 @@ impl EntityTrait for Player {
     fn move_relative(&mut self, xa: f32, za: f32, speed: f32) {
         self.entity.move_relative(xa, za, speed);
     }
+
+    fn texture(&self) -> Option<&str> {
+        Some(&self.model.texture)
+    }
+
+    unsafe fn render(&mut self, _t: &mut Tesselator, a: f32) {
+        let this = &self.entity;
+        glPushMatrix();
+        // the model stands on its feet rather than on the eye
+        glTranslatef(
+            this.xo + (this.x - this.xo) * a,
+            this.yo + (this.y - this.yo) * a - this.height_offset,
+            this.zo + (this.z - this.zo) * a,
+        );
+        let size = 0.058333334;
+        glScalef(1.0, -1.0, 1.0);
+        glScalef(size, size, size);
+        glTranslatef(0.0, -24.0, 0.0);
+        let y_rot = this.y_rot_o + (this.y_rot - this.y_rot_o) * a;
+        let x_rot = this.x_rot_o + (this.x_rot - this.x_rot_o) * a;
+        glRotatef(-y_rot, 0.0, 1.0, 0.0);
+        self.animator.apply(&mut self.model, a);
+        if let Some(head) = self.model.part_mut("head") {
+            head.x_rot = x_rot.to_radians();
+        }
+        if this.hurt_time > 0 {
+            glColor3f(1.0, 0.5, 0.5);
+        }
+        self.model.render();
+        glPopMatrix();
+        glColor3f(1.0, 1.0, 1.0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn player() -> (Player, f32) {
+        let level = Rc::new(RefCell::new(Level::with_dir(64, None)));
+        level.borrow_mut().update_columns(0.0, 0.0);
+        let ground = (level.borrow().depth * 2 / 3 + 1) as f32;
+        let mut player = Player::new(level);
+        player.set_pos(0.5, ground + 3.0, 0.5);
+        (player, ground)
+    }
+
+    #[test]
+    fn double_tapping_jump_toggles_flying() {
+        let (mut player, _) = player();
+        player.jump_pressed();
+        assert!(!player.flying);
+        player.jump_pressed();
+        assert!(player.flying);
+        // too long after the first tap
+        player.jump_pressed();
+        player.jump_tap_ticks = 0;
+        player.jump_pressed();
+        assert!(player.flying);
+        player.jump_pressed();
+        assert!(!player.flying);
+
+        player.controls_enabled = false;
+        player.jump_pressed();
+        player.jump_pressed();
+        assert!(!player.flying);
+    }
+
+    #[test]
+    fn landing_ends_the_flight() {
+        let (mut player, ground) = player();
+        player.flying = true;
+        for _ in 0..20 {
+            player.fly(0.0, 0.0, -1.0);
+        }
+        assert_eq!(player.entity.bb.y0, ground);
+        assert!(!player.flying);
+    }
+
+    #[test]
+    fn noclip_keeps_flying_through_tiles() {
+        let (mut player, ground) = player();
+        player.toggle_noclip();
+        assert!(player.flying && player.entity.noclip);
+        for _ in 0..20 {
+            player.fly(0.0, 0.0, -1.0);
+        }
+        assert!(player.flying);
+        assert!(player.entity.bb.y0 < ground - 1.0);
+        player.toggle_noclip();
+        assert!(!player.entity.noclip);
+    }
 }
@@ -0,0 +1,149 @@
+use std::cell::RefCell;
+use std::fs;
+use std::path::Path;
+use std::rc::Rc;
+
+use serde::{Deserialize, Serialize};
+
+use crate::character::zombie::Zombie;
+use crate::falling_tile::FallingTile;
+use crate::level::level::Level;
+
+/// The file in a world's directory the entities are saved to.
+const FILE: &str = "entities.json";
+
+/// What every entity saves.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct EntityData {
+    pub pos: [f32; 3],
+    pub motion: [f32; 3],
+    pub rot: [f32; 2], // y_rot, x_rot
+    pub health: i32,
+    pub hurt_time: i32,
+    pub fall_distance: f32,
+}
+
+/// An entity as saved, with its type and whatever else the type needs to pick up where it left.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SavedEntity {
+    Player {
+        #[serde(flatten)]
+        entity: EntityData,
+        flying: bool,
+        noclip: bool,
+    },
+    Zombie {
+        #[serde(flatten)]
+        entity: EntityData,
+        head_y_rot: f32,
+        attack_ticks: i32,
+        death_time: i32,
+        wander_rot: f32,
+        wander_rot_a: f32,
+    },
+    FallingTile {
+        #[serde(flatten)]
+        entity: EntityData,
+        tile: i32,
+    },
+}
+
+impl SavedEntity {
+    /// Puts the entity back into `level`, unless it is the player who is not kept there.
+    pub fn restore(&self, level: &Rc<RefCell<Level>>) {
+        match self {
+            SavedEntity::Player { .. } => {}
+            SavedEntity::Zombie { .. } => {
+                let mut zombie = Zombie::new(Rc::clone(level), 0.0, 0.0, 0.0);
+                zombie.load(self);
+                level.borrow_mut().add_entity(zombie);
+            }
+            SavedEntity::FallingTile { entity, tile } => {
+                let mut falling_tile = FallingTile::new(Rc::clone(level), 0, 0, 0, *tile);
+                falling_tile.entity.load(entity);
+                level.borrow_mut().add_entity(falling_tile);
+            }
+        }
+    }
+}
+
+/// The player and the entities in the level, as saved next to the level's regions.
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct SavedEntities {
+    pub player: Option<SavedEntity>,
+    pub entities: Vec<SavedEntity>,
+}
+
+impl SavedEntities {
+    /// The entities saved in the world at `dir`, none if they never were. Fails if the file is
+    /// not what `save` writes, e.g. when it was cut short or edited by hand.
+    pub fn load(dir: &Path) -> Result<Option<SavedEntities>, serde_json::Error> {
+        let json = match fs::read_to_string(dir.join(FILE)) {
+            Ok(json) => json,
+            Err(_) => return Ok(None),
+        };
+        serde_json::from_str(&json).map(Some)
+    }
+
+    pub fn save(&self, dir: &Path) {
+        fs::create_dir_all(dir).unwrap();
+        fs::write(dir.join(FILE), serde_json::to_string(self).unwrap()).unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::process;
+
+    use super::*;
+
+    fn saved(level: &Rc<RefCell<Level>>) -> Vec<SavedEntity> {
+        level
+            .borrow()
+            .entities()
+            .into_iter()
+            .filter_map(|(_, entity)| entity.borrow().save())
+            .collect()
+    }
+
+    #[test]
+    fn entities_come_back_as_they_were_saved() {
+        let level = Rc::new(RefCell::new(Level::with_dir(64, None)));
+        let mut zombie = Zombie::new(Rc::clone(&level), 0.0, 0.0, 0.0);
+        zombie.entity.load(&EntityData {
+            pos: [3.5, 50.0, -2.25],
+            motion: [0.01, -0.2, 0.0],
+            rot: [123.0, -10.0],
+            health: 7,
+            hurt_time: 4,
+            fall_distance: 1.5,
+        });
+        level.borrow_mut().add_entity(zombie);
+        let falling_tile = FallingTile::new(Rc::clone(&level), 1, 40, 1, 11);
+        level.borrow_mut().add_entity(falling_tile);
+
+        let dir = env::temp_dir().join(format!("rd-132328-entities-{}", process::id()));
+        let entities = SavedEntities {
+            player: None,
+            entities: saved(&level),
+        };
+        entities.save(&dir);
+        let loaded = SavedEntities::load(&dir).unwrap().unwrap();
+        // cut short
+        let json = fs::read_to_string(dir.join(FILE)).unwrap();
+        fs::write(dir.join(FILE), &json[..json.len() / 2]).unwrap();
+        let broken = SavedEntities::load(&dir);
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(broken.is_err());
+        assert!(SavedEntities::load(&dir).unwrap().is_none());
+        assert_eq!(loaded, entities);
+
+        let restored = Rc::new(RefCell::new(Level::with_dir(64, None)));
+        for entity in &loaded.entities {
+            entity.restore(&restored);
+        }
+        assert_eq!(saved(&restored), entities.entities);
+    }
+}
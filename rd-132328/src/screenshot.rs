@@ -0,0 +1,144 @@
+use std::cell::RefCell;
+use std::fs;
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use image::RgbaImage;
+use lwrgl::LWRGL;
+
+use crate::camera::Camera;
+use crate::glu::*;
+use crate::level::level::Level;
+use crate::level::level_listener::LevelListener;
+use crate::level::level_renderer::LevelRenderer;
+use crate::level::tile;
+use crate::textures::clear_textures;
+
+/// Reads the current contents of the framebuffer into an image.
+pub fn grab(width: i32, height: i32) -> RgbaImage {
+    let mut pixels = vec![0u8; (width * height * 4) as usize];
+    unsafe {
+        glPixelStorei(GL_PACK_ALIGNMENT, 1);
+        glReadPixels(
+            0,
+            0,
+            width,
+            height,
+            GL_RGBA,
+            GL_UNSIGNED_BYTE,
+            pixels.as_mut_ptr() as *mut GLvoid,
+        );
+    }
+    let mut img = RgbaImage::from_raw(width as u32, height as u32, pixels).unwrap();
+    // OpenGL's rows start at the bottom
+    image::imageops::flip_vertical_in_place(&mut img);
+    for pixel in img.pixels_mut() {
+        pixel[3] = 255;
+    }
+    img
+}
+
+/// Saves the framebuffer to a timestamped png in `screenshots/` and returns its path.
+pub fn take_screenshot(width: i32, height: i32) -> PathBuf {
+    let dir = PathBuf::from("screenshots");
+    fs::create_dir_all(&dir).unwrap();
+    let timestamp = timestamp();
+    let mut path = dir.join(format!("{}.png", timestamp));
+    let mut i = 1;
+    while path.exists() {
+        path = dir.join(format!("{}_{}.png", timestamp, i));
+        i += 1;
+    }
+    grab(width, height).save(&path).unwrap();
+    path
+}
+
+/// Renders a single frame of `level` as seen by `camera` in an invisible window.
+pub fn render_frame(
+    level: Rc<RefCell<Level>>,
+    camera: &Camera,
+    width: i32,
+    height: i32,
+) -> RgbaImage {
+    let lwrgl = LWRGL::new_hidden(width as u32, height as u32);
+    let level_renderer = LevelRenderer::new(Rc::clone(&level));
+    level.borrow_mut().update_columns(camera.x, camera.z);
+    level_renderer.borrow_mut().rebuild_all();
+    let mut camera = camera.clone();
+    camera.aspect = width as f32 / height as f32;
+    let frustum = camera.frustum();
+
+    unsafe {
+        glViewport(0, 0, width, height);
+        glEnable(GL_TEXTURE_2D);
+        glShadeModel(GL_SMOOTH);
+        glClearColor(0.5, 0.8, 1.0, 0.0);
+        glClearDepth(1.0);
+        glEnable(GL_DEPTH_TEST);
+        glDepthFunc(GL_LEQUAL);
+        glClear(GL_COLOR_BUFFER_BIT | GL_DEPTH_BUFFER_BIT);
+        glMatrixMode(GL_PROJECTION);
+        glLoadMatrixf(camera.projection_matrix().as_ptr());
+        glMatrixMode(GL_MODELVIEW);
+        glLoadMatrixf(camera.view_matrix().as_ptr());
+        glEnable(GL_CULL_FACE);
+        glFogi(GL_FOG_MODE, GL_EXP as i32);
+        glFogf(GL_FOG_DENSITY, 0.2);
+        glFogfv(GL_FOG_COLOR, crate::fog_color().as_ptr());
+        glDisable(GL_FOG);
+        level_renderer.borrow_mut().render(&frustum, 0);
+        glEnable(GL_FOG);
+        level_renderer.borrow_mut().render(&frustum, 1);
+        glDisable(GL_FOG);
+        glEnable(GL_BLEND);
+        glBlendFunc(GL_SRC_ALPHA, GL_ONE_MINUS_SRC_ALPHA);
+        level_renderer
+            .borrow_mut()
+            .render(&frustum, tile::TRANSLUCENT_LAYER);
+        glDisable(GL_BLEND);
+        glDisable(GL_FOG);
+        glFinish();
+    }
+
+    let img = grab(width, height);
+    // free the chunks' display lists while the context still exists
+    level
+        .borrow_mut()
+        .remove_listener(&(level_renderer.clone() as Rc<RefCell<dyn LevelListener>>));
+    drop(level_renderer);
+    drop(lwrgl);
+    clear_textures();
+    img
+}
+
+/// The current UTC time as `yyyy-mm-dd_hh.mm.ss`.
+fn timestamp() -> String {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs() as i64;
+    let days = secs.div_euclid(86400);
+    let time = secs.rem_euclid(86400);
+
+    // days since 1970-01-01 to a civil date, see http://howardhinnant.github.io/date_algorithms.html
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{:04}-{:02}-{:02}_{:02}.{:02}.{:02}",
+        year,
+        month,
+        day,
+        time / 3600,
+        time / 60 % 60,
+        time % 60
+    )
+}
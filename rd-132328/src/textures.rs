@@ -48,3 +48,8 @@ pub fn load_texture(resource_name: &str, mode: i32) -> i32 {
 
     id as i32
 }
+
+/// Forgets all loaded textures, for when their GL context has been destroyed.
+pub fn clear_textures() {
+    ID_MAP.lock().unwrap().clear();
+}
@@ -4,10 +4,6 @@ const NS_PER_SECOND: i64 = 1000000000;
 const MAX_NS_PER_UPDATE: i64 = 1000000000;
 const MAX_TICKS_PER_UPDATE: u32 = 100;
 
-lazy_static! {
-    pub static ref PROGRAM_START: Instant = Instant::now();
-}
-
 pub struct Timer {
     ticks_per_second: f32,
     last_time: Instant,